@@ -0,0 +1,142 @@
+use ethers::prelude::*;
+use ethers::utils::get_contract_address;
+
+use crate::authorization::authorization_list;
+
+/// Highest precompile address on mainnet (BLS12-381 map to G2 since Prague)
+const LAST_PRECOMPILE: u64 = 0x11;
+
+/// Size of the access list of a transaction
+#[derive(Debug, Clone, Copy, Default)]
+pub struct AccessListSize {
+    pub addresses: usize,
+    pub storage_keys: usize,
+}
+
+impl AccessListSize {
+    /// Size of a transaction's access list, `None` if it has none or it is empty
+    pub fn of(tx: &Transaction) -> Option<Self> {
+        let items = &tx.access_list.as_ref()?.0;
+        if items.is_empty() {
+            return None;
+        }
+        Some(AccessListSize {
+            addresses: items.len(),
+            storage_keys: items.iter().map(|item| item.storage_keys.len()).sum(),
+        })
+    }
+}
+
+/// Addresses in a transaction's access list it does not obviously interact with
+///
+/// Sender, recipient (or created contract), precompiles, EIP-7702 delegates and addresses found in
+/// the calldata count as related, anything else is likely a stale or mistaken entry paying for
+/// warm access without benefit.
+pub fn unrelated_addresses(tx: &Transaction) -> Vec<Address> {
+    let Some(access_list) = &tx.access_list else {
+        return Vec::new();
+    };
+    let recipient = tx.to.unwrap_or_else(|| get_contract_address(tx.from, tx.nonce));
+    let delegates: Vec<Address> = authorization_list(tx)
+        .unwrap_or_default()
+        .iter()
+        .map(|authorization| authorization.address)
+        .collect();
+
+    access_list
+        .0
+        .iter()
+        .map(|item| item.address)
+        .filter(|address| {
+            *address != tx.from
+                && *address != recipient
+                && *address > Address::from_low_u64_be(LAST_PRECOMPILE)
+                && !delegates.contains(address)
+                && !tx.input.0.windows(Address::len_bytes()).any(|window| window == address.as_bytes())
+        })
+        .collect()
+}
+
+/// Access list usage accumulated over every observed transaction
+#[derive(Debug, Clone, Default)]
+pub struct AccessListStats {
+    pub transactions: u64,
+    /// Transactions with a non-empty access list
+    pub with_access_list: u64,
+    pub addresses: u64,
+    pub storage_keys: u64,
+    /// Transactions whose access list has addresses they do not obviously interact with
+    pub with_unrelated: u64,
+}
+
+impl AccessListStats {
+    pub fn record(&mut self, tx: &Transaction) {
+        self.transactions += 1;
+        if let Some(size) = AccessListSize::of(tx) {
+            self.with_access_list += 1;
+            self.addresses += size.addresses as u64;
+            self.storage_keys += size.storage_keys as u64;
+            if !unrelated_addresses(tx).is_empty() {
+                self.with_unrelated += 1;
+            }
+        }
+    }
+
+    /// Share of transactions using an access list in percent
+    pub fn usage_percent(&self) -> f64 {
+        if self.transactions == 0 {
+            return 0.0;
+        }
+        self.with_access_list as f64 * 100.0 / self.transactions as f64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ethers::types::transaction::eip2930::{AccessList, AccessListItem};
+
+    fn entry(address: Address, storage_keys: usize) -> AccessListItem {
+        AccessListItem { address, storage_keys: vec![H256::zero(); storage_keys] }
+    }
+
+    #[test]
+    fn warns_only_about_addresses_the_transaction_does_not_touch() {
+        let (sender, pool, token, stray) = (Address::repeat_byte(1), Address::repeat_byte(2), Address::repeat_byte(3), Address::repeat_byte(4));
+        let mut input = vec![0xa9, 0x05, 0x9c, 0xbb, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+        input.extend_from_slice(token.as_bytes());
+        let tx = Transaction {
+            from: sender,
+            to: Some(pool),
+            input: input.into(),
+            access_list: Some(AccessList(vec![
+                entry(sender, 0),
+                entry(pool, 3),
+                entry(token, 1),
+                entry(Address::from_low_u64_be(LAST_PRECOMPILE), 0),
+                entry(stray, 2),
+            ])),
+            ..Default::default()
+        };
+
+        assert_eq!(unrelated_addresses(&tx), vec![stray]);
+        let size = AccessListSize::of(&tx).unwrap();
+        assert_eq!((size.addresses, size.storage_keys), (5, 6));
+    }
+
+    #[test]
+    fn summarizes_access_list_usage() {
+        let mut stats = AccessListStats::default();
+        stats.record(&Transaction::default());
+        stats.record(&Transaction { access_list: Some(AccessList(Vec::new())), ..Default::default() });
+        stats.record(&Transaction {
+            to: Some(Address::repeat_byte(2)),
+            access_list: Some(AccessList(vec![entry(Address::repeat_byte(2), 2), entry(Address::repeat_byte(9), 1)])),
+            ..Default::default()
+        });
+
+        assert_eq!((stats.transactions, stats.with_access_list, stats.with_unrelated), (3, 1, 1));
+        assert_eq!((stats.addresses, stats.storage_keys), (2, 3));
+        assert!((stats.usage_percent() - 100.0 / 3.0).abs() < 1e-9);
+    }
+}
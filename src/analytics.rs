@@ -0,0 +1,219 @@
+use ethers::prelude::*;
+use std::cmp::Reverse;
+use std::collections::HashMap;
+
+/// Number of gas consumers listed per block
+const TOP_CONSUMERS: usize = 5;
+
+/// Transactions per EIP-2718 type
+#[derive(Debug, Clone, Default)]
+pub struct TxTypeMix {
+    /// Type 0
+    pub legacy: usize,
+    /// Type 1, EIP-2930
+    pub access_list: usize,
+    /// Type 2, EIP-1559
+    pub dynamic_fee: usize,
+    /// Type 3, EIP-4844
+    pub blob: usize,
+    /// Type 4, EIP-7702
+    pub set_code: usize,
+    pub other: usize,
+}
+
+impl TxTypeMix {
+    fn record(&mut self, tx_type: Option<U64>) {
+        match tx_type.map(|tx_type| tx_type.as_u64()) {
+            None | Some(0) => self.legacy += 1,
+            Some(1) => self.access_list += 1,
+            Some(2) => self.dynamic_fee += 1,
+            Some(3) => self.blob += 1,
+            Some(4) => self.set_code += 1,
+            Some(_) => self.other += 1,
+        }
+    }
+
+    fn add(&mut self, other: &TxTypeMix) {
+        self.legacy += other.legacy;
+        self.access_list += other.access_list;
+        self.dynamic_fee += other.dynamic_fee;
+        self.blob += other.blob;
+        self.set_code += other.set_code;
+        self.other += other.other;
+    }
+}
+
+/// Gas used by the transactions sent to one address
+#[derive(Debug, Clone)]
+pub struct GasConsumer {
+    /// `None` for contract creations
+    pub to: Option<Address>,
+    pub gas_used: U256,
+    pub transactions: usize,
+}
+
+/// Fee economics of a block computed from its full transactions
+#[derive(Debug, Clone)]
+pub struct BlockAnalytics {
+    pub tx_types: TxTypeMix,
+    /// Effective tips times gas used, paid to the fee recipient, in wei
+    pub priority_fees: U256,
+    /// Base fee times gas used, in wei
+    pub burned: U256,
+    /// Recipients using the most gas, highest first
+    pub top_consumers: Vec<GasConsumer>,
+    /// Median effective tip per gas, in wei
+    pub median_tip: Option<U256>,
+    /// Receipts were unavailable, so gas limits stand in for the gas used
+    pub gas_estimated: bool,
+}
+
+impl BlockAnalytics {
+    /// Analyse a block, `receipts` provide the gas used and effective gas price of each transaction
+    pub fn new(block: &Block<Transaction>, receipts: Option<&[TransactionReceipt]>) -> Self {
+        let base_fee = block.base_fee_per_gas.unwrap_or_default();
+        let receipts: HashMap<H256, &TransactionReceipt> = receipts
+            .unwrap_or_default()
+            .iter()
+            .map(|receipt| (receipt.transaction_hash, receipt))
+            .collect();
+        let gas_estimated = receipts.is_empty() && !block.transactions.is_empty();
+
+        let mut tx_types = TxTypeMix::default();
+        let mut priority_fees = U256::zero();
+        let mut tips = Vec::with_capacity(block.transactions.len());
+        let mut consumers: HashMap<Option<Address>, GasConsumer> = HashMap::new();
+
+        for tx in &block.transactions {
+            tx_types.record(tx.transaction_type);
+
+            let receipt = receipts.get(&tx.hash);
+            let gas_used = receipt.and_then(|receipt| receipt.gas_used).unwrap_or(tx.gas);
+            let tip = match receipt.and_then(|receipt| receipt.effective_gas_price) {
+                Some(price) => price.saturating_sub(base_fee),
+                None => effective_tip(tx, base_fee),
+            };
+            priority_fees = priority_fees.saturating_add(tip.saturating_mul(gas_used));
+            tips.push(tip);
+
+            let consumer = consumers.entry(tx.to).or_insert_with(|| GasConsumer {
+                to: tx.to,
+                gas_used: U256::zero(),
+                transactions: 0,
+            });
+            consumer.gas_used += gas_used;
+            consumer.transactions += 1;
+        }
+
+        tips.sort_unstable();
+        let mut top_consumers: Vec<GasConsumer> = consumers.into_values().collect();
+        top_consumers.sort_by_key(|consumer| Reverse(consumer.gas_used));
+        top_consumers.truncate(TOP_CONSUMERS);
+
+        BlockAnalytics {
+            tx_types,
+            priority_fees,
+            burned: base_fee.saturating_mul(block.gas_used),
+            top_consumers,
+            median_tip: tips.get(tips.len() / 2).copied(),
+            gas_estimated,
+        }
+    }
+}
+
+/// Tip per gas a transaction pays to the fee recipient at the given base fee
+pub fn effective_tip(tx: &Transaction, base_fee: U256) -> U256 {
+    match (tx.max_fee_per_gas, tx.max_priority_fee_per_gas) {
+        (Some(max_fee), Some(max_tip)) => max_tip.min(max_fee.saturating_sub(base_fee)),
+        _ => tx.gas_price.unwrap_or_default().saturating_sub(base_fee),
+    }
+}
+
+/// Fee economics accumulated over every analysed block
+#[derive(Debug, Clone, Default)]
+pub struct BlockEconomics {
+    pub blocks: u64,
+    pub tx_types: TxTypeMix,
+    pub priority_fees: U256,
+    pub burned: U256,
+}
+
+impl BlockEconomics {
+    pub fn record(&mut self, analytics: &BlockAnalytics) {
+        self.blocks += 1;
+        self.tx_types.add(&analytics.tx_types);
+        self.priority_fees = self.priority_fees.saturating_add(analytics.priority_fees);
+        self.burned = self.burned.saturating_add(analytics.burned);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn gwei(amount: u64) -> U256 {
+        U256::from(amount) * U256::exp10(9)
+    }
+
+    fn dynamic_fee_tx(hash: u8, to: u8, max_fee: u64, tip: u64) -> Transaction {
+        Transaction {
+            hash: H256::repeat_byte(hash),
+            to: Some(Address::repeat_byte(to)),
+            gas: U256::from(50_000),
+            transaction_type: Some(2.into()),
+            max_fee_per_gas: Some(gwei(max_fee)),
+            max_priority_fee_per_gas: Some(gwei(tip)),
+            ..Default::default()
+        }
+    }
+
+    fn receipt(hash: u8, gas_used: u64, effective_gas_price: u64) -> TransactionReceipt {
+        TransactionReceipt {
+            transaction_hash: H256::repeat_byte(hash),
+            gas_used: Some(gas_used.into()),
+            effective_gas_price: Some(gwei(effective_gas_price)),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn caps_the_effective_tip_at_the_fee_headroom() {
+        assert_eq!(effective_tip(&dynamic_fee_tx(1, 1, 30, 2), gwei(10)), gwei(2));
+        assert_eq!(effective_tip(&dynamic_fee_tx(1, 1, 11, 2), gwei(10)), gwei(1));
+        assert_eq!(effective_tip(&dynamic_fee_tx(1, 1, 9, 2), gwei(10)), U256::zero());
+        let legacy = Transaction { gas_price: Some(gwei(15)), ..Default::default() };
+        assert_eq!(effective_tip(&legacy, gwei(10)), gwei(5));
+    }
+
+    #[test]
+    fn splits_block_fees_into_burned_and_priority_fees() {
+        let legacy = Transaction {
+            transaction_type: None,
+            max_fee_per_gas: None,
+            max_priority_fee_per_gas: None,
+            gas_price: Some(gwei(13)),
+            ..dynamic_fee_tx(3, 0xb, 0, 0)
+        };
+        let block = Block {
+            base_fee_per_gas: Some(gwei(10)),
+            gas_used: U256::from(100_000),
+            transactions: vec![dynamic_fee_tx(1, 0xa, 30, 2), dynamic_fee_tx(2, 0xa, 30, 1), legacy],
+            ..Default::default()
+        };
+        let receipts = [receipt(1, 40_000, 12), receipt(2, 20_000, 11), receipt(3, 40_000, 13)];
+
+        let analytics = BlockAnalytics::new(&block, Some(&receipts));
+        assert_eq!(analytics.burned, gwei(10) * 100_000);
+        assert_eq!(analytics.priority_fees, gwei(2) * 40_000 + gwei(1) * 20_000 + gwei(3) * 40_000);
+        assert_eq!(analytics.median_tip, Some(gwei(2)));
+        assert_eq!((analytics.tx_types.dynamic_fee, analytics.tx_types.legacy), (2, 1));
+        assert_eq!(analytics.top_consumers[0].to, Some(Address::repeat_byte(0xa)));
+        assert_eq!((analytics.top_consumers[0].gas_used, analytics.top_consumers[0].transactions), (U256::from(60_000), 2));
+        assert!(!analytics.gas_estimated);
+
+        // Without receipts the gas limit and the fee caps stand in
+        let estimated = BlockAnalytics::new(&block, None);
+        assert!(estimated.gas_estimated);
+        assert_eq!(estimated.priority_fees, gwei(2) * 50_000 + gwei(1) * 50_000 + gwei(3) * 50_000);
+    }
+}
@@ -0,0 +1,163 @@
+use ethers::prelude::*;
+use ethers::utils::{keccak256, rlp::RlpStream};
+use serde::Deserialize;
+
+/// Transaction type of EIP-7702 set-code transactions
+pub const SET_CODE_TX_TYPE: u64 = 4;
+
+/// Prefix byte of the message signed by an authorization
+const AUTHORIZATION_MAGIC: u8 = 0x05;
+
+/// Half the order of secp256k1, larger `s` values are rejected as malleable
+const SECP256K1N_HALF: &str = "7fffffffffffffffffffffffffffffff5d576e7357a4501ddfe92f46681b20a0";
+
+/// One entry of the authorization list of a type 4 transaction
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Authorization {
+    /// Chain the authorization is valid on, zero for any chain
+    pub chain_id: U256,
+    /// Contract whose code the authority delegates to, the zero address clears the delegation
+    pub address: Address,
+    /// Nonce of the authority at which the authorization applies
+    pub nonce: U64,
+    pub y_parity: U64,
+    pub r: U256,
+    pub s: U256,
+}
+
+impl Authorization {
+    /// Hash signed by the authority, `keccak256(0x05 || rlp([chain_id, address, nonce]))`
+    pub fn signing_hash(&self) -> H256 {
+        let mut stream = RlpStream::new_list(3);
+        stream.append(&self.chain_id);
+        stream.append(&self.address);
+        stream.append(&self.nonce);
+        let mut message = vec![AUTHORIZATION_MAGIC];
+        message.extend_from_slice(&stream.out());
+        H256(keccak256(message))
+    }
+
+    /// Recover the account delegating its code, `None` if the signature is invalid
+    pub fn authority(&self) -> Option<Address> {
+        if self.y_parity.as_u64() > 1 || self.s > U256::from_str_radix(SECP256K1N_HALF, 16).ok()? {
+            return None;
+        }
+        let signature = Signature { r: self.r, s: self.s, v: self.y_parity.as_u64() };
+        signature.recover(RecoveryMessage::Hash(self.signing_hash())).ok()
+    }
+
+    /// Whether the authorization removes the authority's delegation instead of setting one
+    pub fn is_revocation(&self) -> bool {
+        self.address.is_zero()
+    }
+}
+
+/// Whether a transaction is an EIP-7702 set-code transaction
+pub fn is_set_code_transaction(tx: &Transaction) -> bool {
+    tx.transaction_type.is_some_and(|tx_type| tx_type.as_u64() == SET_CODE_TX_TYPE)
+}
+
+/// Decode the authorization list of a transaction, `None` unless it is a set-code transaction
+pub fn authorization_list(tx: &Transaction) -> Option<Vec<Authorization>> {
+    if !is_set_code_transaction(tx) {
+        return None;
+    }
+    Some(tx.other.get_deserialized("authorizationList").and_then(Result::ok).unwrap_or_default())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Order of secp256k1
+    const SECP256K1N: &str = "fffffffffffffffffffffffffffffffebaaedce6af48a03bbfd25e8cd0364141";
+
+    fn wallet() -> LocalWallet {
+        "4c0883a69102937d6231471b5dbb6204fe5129617082792ae468d01a3f362318".parse().unwrap()
+    }
+
+    /// Authorization to `address` signed by `wallet`
+    fn signed(wallet: &LocalWallet, address: Address) -> Authorization {
+        let mut authorization = Authorization {
+            chain_id: U256::one(),
+            address,
+            nonce: U64::from(7),
+            y_parity: U64::zero(),
+            r: U256::zero(),
+            s: U256::zero(),
+        };
+        let signature = wallet.sign_hash(authorization.signing_hash()).unwrap();
+        authorization.y_parity = U64::from(signature.v - 27);
+        authorization.r = signature.r;
+        authorization.s = signature.s;
+        authorization
+    }
+
+    #[test]
+    fn recovers_the_signing_authority() {
+        let wallet = wallet();
+        let authorization = signed(&wallet, Address::repeat_byte(0x63));
+
+        assert_eq!(authorization.authority(), Some(wallet.address()));
+        assert_eq!(
+            authorization.authority(),
+            Some("0x2c7536e3605d9c16a7a3d7b1898e529396a65c23".parse().unwrap())
+        );
+    }
+
+    #[test]
+    fn binds_the_signature_to_the_delegate() {
+        let wallet = wallet();
+        let mut authorization = signed(&wallet, Address::repeat_byte(0x63));
+        authorization.address = Address::repeat_byte(0x64);
+
+        assert_ne!(authorization.authority(), Some(wallet.address()));
+    }
+
+    #[test]
+    fn rejects_y_parity_above_one() {
+        let mut authorization = signed(&wallet(), Address::repeat_byte(0x63));
+        for y_parity in [2, 27, 28] {
+            authorization.y_parity = U64::from(y_parity);
+            assert_eq!(authorization.authority(), None, "y_parity {}", y_parity);
+        }
+    }
+
+    #[test]
+    fn decodes_the_authorization_list_of_set_code_transactions() {
+        let tx: Transaction = serde_json::from_value(serde_json::json!({
+            "hash": H256::zero(), "nonce": "0x0", "from": Address::zero(), "value": "0x0", "gas": "0x0",
+            "input": "0x", "v": "0x0", "r": "0x0", "s": "0x0", "type": "0x4",
+            "authorizationList": [{
+                "chainId": "0x1",
+                "address": "0x63c0c19a282a1b52b07dd5a65b58948a07dae32b",
+                "nonce": "0x7",
+                "yParity": "0x1",
+                "r": "0x80af9d3eb54902575ca9583643b4620e63fe5a5f708baa7d721d5e08eb33a28",
+                "s": "0xe3f0397993db20d6254a0d3aa069c6a7291f885dc00fb675097b6a399038513"
+            }]
+        }))
+        .unwrap();
+
+        let list = authorization_list(&tx).unwrap();
+        assert_eq!(list.len(), 1);
+        assert_eq!(list[0].address, "0x63c0c19a282a1b52b07dd5a65b58948a07dae32b".parse::<Address>().unwrap());
+        assert_eq!(list[0].nonce, U64::from(7));
+        assert_eq!(list[0].y_parity, U64::one());
+        assert!(!list[0].is_revocation());
+    }
+
+    #[test]
+    fn rejects_high_s() {
+        let wallet = wallet();
+        let mut authorization = signed(&wallet, Address::repeat_byte(0x63));
+        // The malleable twin of the signature, (r, n - s) with the other parity, recovers the same key
+        authorization.s = U256::from_str_radix(SECP256K1N, 16).unwrap() - authorization.s;
+        authorization.y_parity = U64::one() - authorization.y_parity;
+        let twin = Signature { r: authorization.r, s: authorization.s, v: authorization.y_parity.as_u64() };
+        assert_eq!(twin.recover(RecoveryMessage::Hash(authorization.signing_hash())).unwrap(), wallet.address());
+
+        assert_eq!(authorization.authority(), None);
+    }
+}
@@ -0,0 +1,147 @@
+use ethers::prelude::*;
+use futures::stream::{self, StreamExt};
+use log::{info, warn};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tokio::sync::mpsc;
+
+use crate::chain::{fetch_with_retry, BlockDetail, BlockEvent, ChainTracker};
+use crate::error::NodeError;
+
+/// Historical blocks to scan before (optionally) following the chain head
+#[derive(Debug, Clone)]
+pub struct ScanRange {
+    pub from: u64,
+    /// Last block to scan, `None` catches up with the head and keeps following it
+    pub to: Option<u64>,
+    /// File recording the last processed block, a restarted scan resumes after it
+    pub checkpoint: Option<PathBuf>,
+}
+
+/// Read the last scanned block from a checkpoint file, `None` if there is none yet
+fn read_checkpoint(path: &Path) -> Result<Option<u64>, NodeError> {
+    match std::fs::read_to_string(path) {
+        Ok(content) => content
+            .trim()
+            .parse()
+            .map(Some)
+            .map_err(|e| NodeError::Other(format!("Invalid checkpoint file {}: {}", path.display(), e))),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(NodeError::Other(format!("Failed to read checkpoint file {}: {}", path.display(), e))),
+    }
+}
+
+/// Record `number` as the last processed block, called by the consumer once it handled the block
+pub fn write_checkpoint(path: &Path, number: u64) -> Result<(), NodeError> {
+    std::fs::write(path, format!("{}\n", number))
+        .map_err(|e| NodeError::Other(format!("Failed to write checkpoint file {}: {}", path.display(), e)))
+}
+
+/// Fetch the blocks `from..=to` with up to `concurrency` lookups in parallel and send them in order
+async fn scan<M: Middleware>(
+    provider: &M,
+    from: u64,
+    to: u64,
+    concurrency: usize,
+    chain: &mut ChainTracker,
+    block_sender: &mpsc::Sender<BlockEvent>,
+) -> Result<(), NodeError> {
+    info!("Scanning blocks {} to {}", from, to);
+
+    let detail = chain.detail();
+    let mut blocks = stream::iter(from..=to)
+        .map(|number| fetch_with_retry(provider, number, detail))
+        .buffered(concurrency.max(1));
+    while let Some(block) = blocks.next().await {
+        let block = block?;
+        chain.push(block.block.clone());
+        if block_sender.send(BlockEvent::New(Box::new(block))).await.is_err() {
+            return Err(NodeError::Other("Block channel closed".to_string()));
+        }
+    }
+    Ok(())
+}
+
+/// Scan a range of historical blocks into the block channel
+///
+/// Every call of the scan is charged against the provider's rate budget, blocks are fetched up to
+/// `concurrency` at a time but always sent in order. A range ending past the head is cut at the
+/// head. An open-ended range is scanned until the head is reached, the returned chain then holds
+/// the latest blocks so the live feed can continue from there.
+pub async fn backfill_blocks<M: Middleware>(
+    provider: Arc<M>,
+    range: &ScanRange,
+    concurrency: usize,
+    detail: BlockDetail,
+    block_sender: &mpsc::Sender<BlockEvent>,
+) -> Result<ChainTracker, NodeError> {
+    let checkpoint = range.checkpoint.as_deref();
+    let mut next = range.from;
+    if let Some(last) = checkpoint.map(read_checkpoint).transpose()?.flatten() {
+        if last >= next {
+            info!("Resuming after block {} from checkpoint", last);
+            next = last + 1;
+        }
+    }
+
+    let mut chain = ChainTracker::new(detail);
+    loop {
+        let head = provider.get_block_number().await.map_err(|e| NodeError::from_middleware(&e))?.as_u64();
+        let to = match range.to {
+            Some(to) if to > head => {
+                warn!("Block {} is not mined yet, scanning up to the head {}", to, head);
+                head
+            }
+            Some(to) => to,
+            None => head,
+        };
+        if next > to {
+            break;
+        }
+        scan(provider.as_ref(), next, to, concurrency, &mut chain, block_sender).await?;
+        next = to + 1;
+        if range.to.is_some() {
+            break;
+        }
+    }
+
+    info!("Block scan complete");
+    Ok(chain)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ethers::providers::MockProvider;
+
+    fn block(number: u64) -> Block<TxHash> {
+        Block { number: Some(number.into()), hash: Some(H256::from_low_u64_be(number)), ..Default::default() }
+    }
+
+    #[tokio::test]
+    async fn resumes_after_the_checkpoint_and_stops_at_the_head() {
+        let path = std::env::temp_dir().join(format!("ultreth-checkpoint-{}", std::process::id()));
+        write_checkpoint(&path, 5).unwrap();
+        let mock = MockProvider::new();
+        // Responses are served last in, first out: the head is requested first, then blocks 6 and 7
+        mock.push(block(7)).unwrap();
+        mock.push(block(6)).unwrap();
+        mock.push(U64::from(7)).unwrap();
+        let range = ScanRange { from: 3, to: Some(100), checkpoint: Some(path.clone()) };
+        let (block_sender, mut block_receiver) = mpsc::channel(10);
+
+        backfill_blocks(Arc::new(Provider::new(mock.clone())), &range, 1, BlockDetail::Hashes, &block_sender).await.unwrap();
+        drop(block_sender);
+
+        let mut numbers = Vec::new();
+        while let Some(BlockEvent::New(new_block)) = block_receiver.recv().await {
+            numbers.push(new_block.block.number.unwrap().as_u64());
+        }
+        assert_eq!(numbers, vec![6, 7]);
+        mock.assert_request("eth_blockNumber", ()).unwrap();
+        mock.assert_request("eth_getBlockByNumber", (U64::from(6), false)).unwrap();
+        // Only the consumer moves the checkpoint
+        assert_eq!(read_checkpoint(&path).unwrap(), Some(5));
+        let _ = std::fs::remove_file(&path);
+    }
+}
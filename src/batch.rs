@@ -0,0 +1,450 @@
+use async_trait::async_trait;
+use ethers::providers::{HttpClientError, JsonRpcClient, JsonRpcError};
+use futures::future::join_all;
+use log::{debug, warn};
+use reqwest::StatusCode;
+use serde::{de::DeserializeOwned, Deserialize, Deserializer, Serialize};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::mem;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::oneshot;
+use tokio::time::sleep;
+use url::Url;
+
+/// JSON-RPC codes of an endpoint rejecting batch requests (Invalid Request, Method not found)
+const BATCH_UNSUPPORTED_CODES: [i64; 2] = [-32600, -32601];
+
+/// JSON-RPC batching settings for HTTP endpoints
+#[derive(Debug, Clone)]
+pub struct BatchConfig {
+    /// Maximum number of calls per HTTP request, 1 disables batching
+    pub max_size: usize,
+    /// How long the first call of a batch waits for others to join
+    pub window: Duration,
+}
+
+impl BatchConfig {
+    /// Whether calls should be coalesced at all
+    pub fn is_enabled(&self) -> bool {
+        self.max_size > 1
+    }
+}
+
+/// A single entry of a JSON-RPC response
+#[derive(Debug, Deserialize)]
+struct Response {
+    #[serde(default)]
+    id: Value,
+    /// `Some(Value::Null)` for a `null` result, `None` if the field is missing
+    #[serde(default, deserialize_with = "present")]
+    result: Option<Value>,
+    error: Option<JsonRpcError>,
+}
+
+impl Response {
+    fn into_result(self, text: impl FnOnce() -> String) -> Result<Value, HttpClientError> {
+        match (self.error, self.result) {
+            (Some(error), _) => Err(HttpClientError::JsonRpcError(error)),
+            (None, Some(result)) => Ok(result),
+            (None, None) => Err(HttpClientError::SerdeJson {
+                err: serde::de::Error::custom("response has neither result nor error"),
+                text: text(),
+            }),
+        }
+    }
+}
+
+/// Keep `null` values of a field apart from a missing field
+fn present<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<Value>, D::Error> {
+    Value::deserialize(deserializer).map(Some)
+}
+
+/// Call waiting in the queue for the next batch
+#[derive(Debug)]
+struct Call {
+    id: u64,
+    method: String,
+    params: Value,
+    respond: oneshot::Sender<Result<Value, HttpClientError>>,
+}
+
+impl Call {
+    fn payload(&self) -> Value {
+        json!({ "jsonrpc": "2.0", "id": self.id, "method": self.method, "params": self.params })
+    }
+}
+
+#[derive(Debug)]
+struct Inner {
+    client: reqwest::Client,
+    url: Url,
+    config: BatchConfig,
+    next_id: AtomicU64,
+    queue: Mutex<Vec<Call>>,
+    /// Cleared once the endpoint rejects a batch request, all later calls are sent on their own
+    batches_supported: AtomicBool,
+}
+
+/// HTTP JSON-RPC client that coalesces calls issued within a short window into batch requests
+///
+/// A batch is sent when it reaches `max_size` calls or when `window` has passed since its first
+/// call. Endpoints that answer a batch with an Invalid Request or Method not found error are assumed
+/// not to support batching, the affected calls are retried one by one and batching is switched off.
+/// Any other error answering the whole batch, e.g. throttling, is returned to each of its calls.
+/// With batching disabled every call is sent on its own. Unlike the ethers HTTP client, throttling
+/// (429) and credential failures (401, 403) keep their status so they can be classified from it.
+#[derive(Debug, Clone)]
+pub struct BatchHttp {
+    inner: Arc<Inner>,
+}
+
+impl BatchHttp {
+    pub fn new(url: Url, config: BatchConfig) -> Self {
+        BatchHttp {
+            inner: Arc::new(Inner {
+                client: reqwest::Client::new(),
+                url,
+                config,
+                next_id: AtomicU64::new(1),
+                queue: Mutex::new(Vec::new()),
+                batches_supported: AtomicBool::new(true),
+            }),
+        }
+    }
+
+    /// Whether calls are batched, i.e. batching is enabled and the endpoint still accepts batch requests
+    pub fn batches_supported(&self) -> bool {
+        self.inner.config.is_enabled() && self.inner.batches_supported.load(Ordering::Relaxed)
+    }
+}
+
+impl Inner {
+    /// Queue a call, starting the batch window or sending a full batch right away
+    fn enqueue(self: &Arc<Self>, call: Call) {
+        let (full, first) = {
+            let mut queue = self.queue.lock().unwrap();
+            queue.push(call);
+            if queue.len() >= self.config.max_size {
+                (Some(mem::take(&mut *queue)), false)
+            } else {
+                (None, queue.len() == 1)
+            }
+        };
+
+        if let Some(calls) = full {
+            tokio::spawn(Arc::clone(self).send_batch(calls));
+        } else if first {
+            let inner = Arc::clone(self);
+            tokio::spawn(async move {
+                sleep(inner.config.window).await;
+                let calls = mem::take(&mut *inner.queue.lock().unwrap());
+                if !calls.is_empty() {
+                    inner.send_batch(calls).await;
+                }
+            });
+        }
+    }
+
+    async fn send_batch(self: Arc<Self>, calls: Vec<Call>) {
+        if calls.len() == 1 || !self.batches_supported.load(Ordering::Relaxed) {
+            self.send_each(calls).await;
+            return;
+        }
+
+        let payload: Vec<Value> = calls.iter().map(Call::payload).collect();
+        debug!("Sending batch of {} calls", payload.len());
+        let body = match self.post(&Value::Array(payload)).await {
+            Ok(body) => body,
+            Err(e) => {
+                // Connection failures are reported per call so each caller sees its own error
+                debug!("Batch request failed, retrying calls individually: {}", e);
+                self.send_each(calls).await;
+                return;
+            }
+        };
+
+        let responses = match serde_json::from_slice::<Vec<Response>>(&body) {
+            Ok(responses) => responses,
+            Err(_) => match serde_json::from_slice::<Response>(&body).map(|response| response.error) {
+                Ok(Some(error)) if BATCH_UNSUPPORTED_CODES.contains(&error.code) => {
+                    // The endpoint does not support batches
+                    if self.batches_supported.swap(false, Ordering::Relaxed) {
+                        warn!("{} rejected a batch request, sending calls individually", self.url);
+                    }
+                    self.send_each(calls).await;
+                    return;
+                }
+                Ok(Some(error)) => {
+                    // Throttling and other failures of the whole request, every call gets the error
+                    for call in calls {
+                        let _ = call.respond.send(Err(HttpClientError::JsonRpcError(error.clone())));
+                    }
+                    return;
+                }
+                _ => {
+                    // Not JSON-RPC at all (e.g. a gateway error page), every call gets the raw body
+                    let text = String::from_utf8_lossy(&body).to_string();
+                    for call in calls {
+                        let _ = call.respond.send(Err(decode_error(&text)));
+                    }
+                    return;
+                }
+            },
+        };
+
+        let mut by_id: HashMap<u64, Response> = responses
+            .into_iter()
+            .filter_map(|response| response.id.as_u64().map(|id| (id, response)))
+            .collect();
+        let mut missing = Vec::new();
+        for call in calls {
+            match by_id.remove(&call.id) {
+                Some(response) => {
+                    let _ = call.respond.send(response.into_result(|| String::from_utf8_lossy(&body).to_string()));
+                }
+                None => missing.push(call),
+            }
+        }
+        if !missing.is_empty() {
+            debug!("Batch response lacked {} calls, retrying them individually", missing.len());
+            self.send_each(missing).await;
+        }
+    }
+
+    /// Send every call in its own request, concurrently
+    async fn send_each(&self, calls: Vec<Call>) {
+        join_all(calls.into_iter().map(|call| async move {
+            let result = self.send_single(&call).await;
+            let _ = call.respond.send(result);
+        }))
+        .await;
+    }
+
+    async fn send_single(&self, call: &Call) -> Result<Value, HttpClientError> {
+        let body = self.post(&call.payload()).await?;
+        match serde_json::from_slice::<Response>(&body) {
+            Ok(response) => response.into_result(|| String::from_utf8_lossy(&body).to_string()),
+            Err(err) => Err(HttpClientError::SerdeJson {
+                err,
+                text: String::from_utf8_lossy(&body).to_string(),
+            }),
+        }
+    }
+
+    async fn post(&self, payload: &Value) -> Result<Vec<u8>, reqwest::Error> {
+        let response = self.client.post(self.url.as_ref()).json(payload).send().await?;
+        // Throttling and credential failures are classified from the status, whatever the body says
+        let response = match response.status() {
+            StatusCode::TOO_MANY_REQUESTS | StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => response.error_for_status()?,
+            _ => response,
+        };
+        Ok(response.bytes().await?.to_vec())
+    }
+}
+
+/// Decoding error for a batch response that is not JSON-RPC
+fn decode_error(text: &str) -> HttpClientError {
+    HttpClientError::SerdeJson {
+        err: serde::de::Error::custom("batch response is not JSON-RPC"),
+        text: text.to_string(),
+    }
+}
+
+#[async_trait]
+impl JsonRpcClient for BatchHttp {
+    type Error = HttpClientError;
+
+    async fn request<T, R>(&self, method: &str, params: T) -> Result<R, HttpClientError>
+    where
+        T: Debug + Serialize + Send + Sync,
+        R: DeserializeOwned + Send,
+    {
+        let params = serde_json::to_value(params).map_err(|err| HttpClientError::SerdeJson {
+            err,
+            text: format!("failed to serialize params of {}", method),
+        })?;
+        let (respond, receiver) = oneshot::channel();
+        let call = Call {
+            id: self.inner.next_id.fetch_add(1, Ordering::Relaxed),
+            method: method.to_string(),
+            params,
+            respond,
+        };
+
+        let value = if self.batches_supported() {
+            self.inner.enqueue(call);
+            receiver.await.map_err(|_| HttpClientError::SerdeJson {
+                err: serde::de::Error::custom("batch dispatcher dropped the call"),
+                text: String::new(),
+            })??
+        } else {
+            self.inner.send_single(&call).await?
+        };
+        R::deserialize(&value).map_err(|err| HttpClientError::SerdeJson {
+            err,
+            text: value.to_string(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+    use tokio::net::{TcpListener, TcpStream};
+    use tokio::task::JoinHandle;
+
+    /// Answer a call with its first parameter
+    fn echo(call: &Value) -> Value {
+        json!({ "jsonrpc": "2.0", "id": call["id"], "result": call["params"][0] })
+    }
+
+    /// Read one HTTP request from the connection and return its JSON body
+    async fn read_request(stream: &mut BufReader<TcpStream>) -> Option<Value> {
+        let mut length = 0;
+        loop {
+            let mut line = String::new();
+            if stream.read_line(&mut line).await.ok()? == 0 {
+                return None;
+            }
+            let line = line.trim_end();
+            if line.is_empty() {
+                break;
+            }
+            if let Some((name, value)) = line.split_once(':') {
+                if name.eq_ignore_ascii_case("content-length") {
+                    length = value.trim().parse().ok()?;
+                }
+            }
+        }
+        let mut body = vec![0; length];
+        stream.read_exact(&mut body).await.ok()?;
+        serde_json::from_slice(&body).ok()
+    }
+
+    /// JSON-RPC HTTP server echoing calls, recording the number of calls in each POST
+    ///
+    /// Batches are answered with a single error carrying `batch_error` instead, if given.
+    async fn serve(batch_error: Option<i64>) -> (Url, Arc<Mutex<Vec<usize>>>, JoinHandle<()>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = Url::parse(&format!("http://{}", listener.local_addr().unwrap())).unwrap();
+        let posts = Arc::new(Mutex::new(Vec::new()));
+        let recorded = Arc::clone(&posts);
+        let server = tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                let posts = Arc::clone(&recorded);
+                tokio::spawn(async move {
+                    let mut stream = BufReader::new(stream);
+                    while let Some(request) = read_request(&mut stream).await {
+                        let response = match &request {
+                            Value::Array(calls) => {
+                                posts.lock().unwrap().push(calls.len());
+                                match batch_error {
+                                    Some(code) => json!({
+                                        "jsonrpc": "2.0", "id": null, "error": { "code": code, "message": "batch failed" }
+                                    }),
+                                    None => Value::Array(calls.iter().map(echo).collect()),
+                                }
+                            }
+                            call => {
+                                posts.lock().unwrap().push(1);
+                                echo(call)
+                            }
+                        };
+                        let body = response.to_string();
+                        let head = format!(
+                            "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\r\n",
+                            body.len()
+                        );
+                        let stream = stream.get_mut();
+                        if stream.write_all(head.as_bytes()).await.is_err() || stream.write_all(body.as_bytes()).await.is_err() {
+                            return;
+                        }
+                    }
+                });
+            }
+        });
+        (url, posts, server)
+    }
+
+    fn client(url: Url, max_size: usize) -> BatchHttp {
+        BatchHttp::new(url, BatchConfig { max_size, window: Duration::from_millis(50) })
+    }
+
+    /// Issue `count` concurrent calls, each echoing its index
+    async fn call_concurrently(client: &BatchHttp, count: u64) -> Vec<Result<u64, HttpClientError>> {
+        join_all((0..count).map(|i| client.request::<_, u64>("echo", [i]))).await
+    }
+
+    fn sorted(posts: &Mutex<Vec<usize>>) -> Vec<usize> {
+        let mut posts = posts.lock().unwrap().clone();
+        posts.sort_unstable();
+        posts
+    }
+
+    #[tokio::test]
+    async fn coalesces_concurrent_calls_into_one_post() {
+        let (url, posts, server) = serve(None).await;
+        let client = client(url, 10);
+
+        let results = call_concurrently(&client, 5).await;
+
+        let results: Vec<u64> = results.into_iter().map(Result::unwrap).collect();
+        assert_eq!(results, vec![0, 1, 2, 3, 4]);
+        assert_eq!(sorted(&posts), vec![5]);
+        server.abort();
+    }
+
+    #[tokio::test]
+    async fn splits_batches_at_max_size() {
+        let (url, posts, server) = serve(None).await;
+        let client = client(url, 2);
+
+        let results = call_concurrently(&client, 5).await;
+
+        assert!(results.iter().all(Result::is_ok));
+        assert_eq!(sorted(&posts), vec![1, 2, 2]);
+        server.abort();
+    }
+
+    #[tokio::test]
+    async fn falls_back_to_single_calls_when_batches_are_rejected() {
+        let (url, posts, server) = serve(Some(-32600)).await;
+        let client = client(url, 10);
+
+        let results = call_concurrently(&client, 3).await;
+
+        let results: Vec<u64> = results.into_iter().map(Result::unwrap).collect();
+        assert_eq!(results, vec![0, 1, 2]);
+        assert!(!client.batches_supported());
+        assert_eq!(sorted(&posts), vec![1, 1, 1, 3]);
+
+        // Later calls skip the batch queue entirely
+        posts.lock().unwrap().clear();
+        assert!(call_concurrently(&client, 2).await.iter().all(Result::is_ok));
+        assert_eq!(sorted(&posts), vec![1, 1]);
+        server.abort();
+    }
+
+    #[tokio::test]
+    async fn keeps_batching_when_a_batch_is_throttled() {
+        let (url, posts, server) = serve(Some(-32005)).await;
+        let client = client(url, 10);
+
+        let results = call_concurrently(&client, 3).await;
+
+        for result in results {
+            match result {
+                Err(HttpClientError::JsonRpcError(error)) => assert_eq!(error.code, -32005),
+                other => panic!("expected the throttling error, got {:?}", other),
+            }
+        }
+        assert!(client.batches_supported());
+        assert_eq!(sorted(&posts), vec![3]);
+        server.abort();
+    }
+}
@@ -0,0 +1,201 @@
+use ethers::prelude::*;
+
+/// Blob gas consumed by each blob (2^17)
+pub const GAS_PER_BLOB: u64 = 131_072;
+
+/// Lowest possible blob base fee in wei
+const MIN_BLOB_BASE_FEE: u64 = 1;
+
+/// Transaction type of blob-carrying transactions
+pub const BLOB_TX_TYPE: u64 = 3;
+
+/// Blob base fee update fractions of Cancun (EIP-4844), Prague (EIP-7691), BPO1 and BPO2
+const CANCUN_FRACTION: u64 = 3_338_477;
+const PRAGUE_FRACTION: u64 = 5_007_716;
+const BPO1_FRACTION: u64 = 8_346_193;
+const BPO2_FRACTION: u64 = 11_684_671;
+
+/// Blob base fee update fraction by activation timestamp of each chain, latest first
+///
+/// Blocks before Cancun carry no excess blob gas, so the Cancun fraction applies from the start.
+const BLOB_SCHEDULES: [(u64, &[(u64, u64)]); 4] = [
+    // Mainnet
+    (1, &[(1_767_747_671, BPO2_FRACTION), (1_765_290_071, BPO1_FRACTION), (1_746_612_311, PRAGUE_FRACTION), (0, CANCUN_FRACTION)]),
+    // Sepolia
+    (11_155_111, &[(1_761_607_008, BPO2_FRACTION), (1_761_017_184, BPO1_FRACTION), (1_741_159_776, PRAGUE_FRACTION), (0, CANCUN_FRACTION)]),
+    // Holesky
+    (17_000, &[(1_760_389_824, BPO2_FRACTION), (1_759_800_000, BPO1_FRACTION), (1_740_434_112, PRAGUE_FRACTION), (0, CANCUN_FRACTION)]),
+    // Hoodi
+    (560_048, &[(1_762_955_544, BPO2_FRACTION), (1_762_365_720, BPO1_FRACTION), (1_742_999_832, PRAGUE_FRACTION), (0, CANCUN_FRACTION)]),
+];
+
+/// Blob fields of a type 3 transaction
+#[derive(Debug, Clone)]
+pub struct BlobFields {
+    pub max_fee_per_blob_gas: Option<U256>,
+    pub versioned_hashes: Vec<H256>,
+}
+
+impl BlobFields {
+    /// Decode the blob fields of a transaction, `None` if it carries no blobs
+    pub fn from_transaction(tx: &Transaction) -> Option<Self> {
+        if !is_blob_transaction(tx) {
+            return None;
+        }
+        Some(BlobFields {
+            max_fee_per_blob_gas: tx.other.get_deserialized("maxFeePerBlobGas").and_then(Result::ok),
+            versioned_hashes: tx
+                .other
+                .get_deserialized("blobVersionedHashes")
+                .and_then(Result::ok)
+                .unwrap_or_default(),
+        })
+    }
+
+    /// Blob gas the transaction consumes
+    pub fn blob_gas(&self) -> u64 {
+        self.versioned_hashes.len() as u64 * GAS_PER_BLOB
+    }
+}
+
+/// Whether a transaction is an EIP-4844 blob transaction
+pub fn is_blob_transaction(tx: &Transaction) -> bool {
+    tx.transaction_type.is_some_and(|tx_type| tx_type.as_u64() == BLOB_TX_TYPE)
+}
+
+/// Number of blobs in a block using `blob_gas_used`
+pub fn blob_count(blob_gas_used: U256) -> u64 {
+    (blob_gas_used / GAS_PER_BLOB).low_u64()
+}
+
+/// Source of the blob base fee of the chain the endpoints serve
+#[derive(Debug, Clone, Copy)]
+pub struct BlobFees {
+    /// Update fraction by activation timestamp, `None` for chains without a known schedule
+    schedule: Option<&'static [(u64, u64)]>,
+}
+
+impl BlobFees {
+    pub fn for_chain(chain_id: U256) -> Self {
+        BlobFees {
+            schedule: BLOB_SCHEDULES
+                .iter()
+                .find(|(id, _)| U256::from(*id) == chain_id)
+                .map(|(_, schedule)| *schedule),
+        }
+    }
+
+    /// Whether the blob base fee is computed locally rather than asked from the node
+    pub fn is_known(&self) -> bool {
+        self.schedule.is_some()
+    }
+
+    /// Blob base fee in wei of a block, `None` if it carries no excess blob gas
+    ///
+    /// Uses the update fraction of the fork active at the block's timestamp. On chains without a
+    /// known schedule the node's `eth_blobBaseFee` is used, the fee of the block after its head.
+    pub async fn base_fee<M: Middleware>(&self, provider: &M, block: &Block<TxHash>) -> Option<U256> {
+        let excess_blob_gas = block.excess_blob_gas?;
+        match self.schedule {
+            Some(schedule) => Some(blob_base_fee(excess_blob_gas, update_fraction(schedule, block.timestamp))),
+            None => provider.provider().request("eth_blobBaseFee", ()).await.ok(),
+        }
+    }
+}
+
+/// Update fraction of the fork active at `timestamp`
+fn update_fraction(schedule: &[(u64, u64)], timestamp: U256) -> u64 {
+    let timestamp = timestamp.low_u64();
+    schedule
+        .iter()
+        .find(|(activation, _)| timestamp >= *activation)
+        .map_or(CANCUN_FRACTION, |(_, fraction)| *fraction)
+}
+
+/// Blob base fee in wei for the given excess blob gas and update fraction
+fn blob_base_fee(excess_blob_gas: U256, update_fraction: u64) -> U256 {
+    fake_exponential(U256::from(MIN_BLOB_BASE_FEE), excess_blob_gas, U256::from(update_fraction))
+}
+
+/// Integer approximation of `factor * e ** (numerator / denominator)` as specified in EIP-4844
+fn fake_exponential(factor: U256, numerator: U256, denominator: U256) -> U256 {
+    let mut output = U256::zero();
+    let mut accumulator = factor * denominator;
+    let mut i = U256::one();
+    while !accumulator.is_zero() {
+        output = output.saturating_add(accumulator);
+        accumulator = accumulator.saturating_mul(numerator) / (denominator * i);
+        i += U256::one();
+    }
+    output / denominator
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fake_exponential_matches_the_reference_values() {
+        // (factor, numerator, denominator, expected) as computed by the EIP-4844 reference implementation
+        let cases: [(u64, u64, u64, u64); 16] = [
+            (1, 0, 1, 1),
+            (38_493, 0, 1_000, 38_493),
+            (0, 1_234, 2_345, 0),
+            (1, 2, 1, 6),
+            (1, 4, 2, 6),
+            (1, 3, 1, 16),
+            (1, 6, 2, 18),
+            (1, 4, 1, 49),
+            (1, 8, 2, 50),
+            (10, 8, 2, 542),
+            (11, 8, 2, 596),
+            (1, 5, 1, 136),
+            (1, 5, 2, 11),
+            (2, 5, 2, 23),
+            (1, 50_000_000, 2_225_652, 5_709_098_764),
+            (1, 380_928, 3_338_477, 1),
+        ];
+        for (factor, numerator, denominator, expected) in cases {
+            let output = fake_exponential(factor.into(), numerator.into(), denominator.into());
+            assert_eq!(output, U256::from(expected), "fake_exponential({}, {}, {})", factor, numerator, denominator);
+        }
+    }
+
+    #[test]
+    fn switches_update_fraction_at_each_fork() {
+        let mainnet = BlobFees::for_chain(U256::one()).schedule.unwrap();
+        let at = |timestamp: u64| update_fraction(mainnet, timestamp.into());
+        assert_eq!(at(1_710_338_135), CANCUN_FRACTION);
+        assert_eq!(at(1_746_612_310), CANCUN_FRACTION);
+        assert_eq!(at(1_746_612_311), PRAGUE_FRACTION);
+        assert_eq!(at(1_765_290_070), PRAGUE_FRACTION);
+        assert_eq!(at(1_765_290_071), BPO1_FRACTION);
+        assert_eq!(at(1_767_747_670), BPO1_FRACTION);
+        assert_eq!(at(1_767_747_671), BPO2_FRACTION);
+        assert_eq!(at(u64::MAX), BPO2_FRACTION);
+    }
+
+    #[test]
+    fn asks_the_node_on_unknown_chains() {
+        assert!(BlobFees::for_chain(U256::from(11_155_111)).is_known());
+        assert!(!BlobFees::for_chain(U256::from(8_453)).is_known());
+    }
+
+    #[tokio::test]
+    async fn takes_the_blob_base_fee_from_the_schedule_or_the_node() {
+        let mock = ethers::providers::MockProvider::new();
+        mock.push(U256::from(42)).unwrap();
+        let provider = Provider::new(mock);
+        let block = Block::<TxHash> {
+            excess_blob_gas: Some(U256::from(10 * CANCUN_FRACTION)),
+            timestamp: U256::from(1_710_338_135),
+            ..Default::default()
+        };
+
+        // e^10 wei on mainnet at Cancun
+        let mainnet = BlobFees::for_chain(U256::one()).base_fee(&provider, &block).await;
+        assert_eq!(mainnet, Some(U256::from(22_026)));
+        assert_eq!(BlobFees::for_chain(U256::from(8_453)).base_fee(&provider, &block).await, Some(U256::from(42)));
+        assert_eq!(BlobFees::for_chain(U256::one()).base_fee(&provider, &Block::default()).await, None);
+    }
+}
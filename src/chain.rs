@@ -0,0 +1,461 @@
+use ethers::prelude::*;
+use log::{debug, warn};
+use std::collections::VecDeque;
+use std::fmt::Debug;
+use std::time::Duration;
+use tokio::time::sleep;
+
+use crate::analytics::BlockAnalytics;
+use crate::error::NodeError;
+
+/// Number of recent canonical blocks remembered, reorgs deeper than this reset the tracked chain
+const MAX_DEPTH: usize = 128;
+
+/// Attempts per block before giving up on it
+const MAX_ATTEMPTS: u32 = 3;
+
+/// Delay between two attempts to fetch the same block
+const RETRY_DELAY: Duration = Duration::from_millis(500);
+
+/// Change of the canonical chain delivered by the block feeds
+#[derive(Debug, Clone)]
+pub enum BlockEvent {
+    /// A block joined the canonical chain, in order of block number
+    New(Box<NewBlock>),
+    /// Blocks left the canonical chain, sent before the blocks of the new branch
+    Reorg(Reorg),
+}
+
+/// Block delivered by the block feeds
+#[derive(Debug, Clone)]
+pub struct NewBlock {
+    pub block: Block<TxHash>,
+    /// Only computed when the feeds fetch full transactions
+    pub analytics: Option<BlockAnalytics>,
+}
+
+/// How much of each block the block feeds fetch
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum BlockDetail {
+    /// Transaction hashes only (`eth_getBlockByNumber`/`eth_getBlockByHash`)
+    #[default]
+    Hashes,
+    /// Full transactions and receipts, for per-block analytics
+    Full,
+}
+
+/// Fetch a block at the given level of detail
+///
+/// Full blocks also fetch the block's receipts with `eth_getBlockReceipts`. Endpoints without it
+/// still get analytics, with transaction gas limits standing in for the gas used.
+pub async fn fetch_block<M: Middleware>(
+    provider: &M,
+    id: impl Into<BlockId> + Send + Sync,
+    detail: BlockDetail,
+) -> Result<Option<NewBlock>, NodeError> {
+    match detail {
+        BlockDetail::Hashes => {
+            let block = provider.get_block(id).await.map_err(|e| NodeError::from_middleware(&e))?;
+            Ok(block.map(|block| NewBlock { block, analytics: None }))
+        }
+        BlockDetail::Full => {
+            let Some(block) = provider.get_block_with_txs(id).await.map_err(|e| NodeError::from_middleware(&e))? else {
+                return Ok(None);
+            };
+            let receipts = match block.number {
+                Some(number) => match provider.get_block_receipts(number).await {
+                    // Receipts by number may belong to another branch during a reorg
+                    Ok(receipts) if receipts.iter().all(|receipt| receipt.block_hash == block.hash) => Some(receipts),
+                    Ok(_) => None,
+                    Err(e) => {
+                        debug!("Failed to fetch receipts of block {}: {}", number, NodeError::from_middleware(&e));
+                        None
+                    }
+                },
+                None => None,
+            };
+            let analytics = BlockAnalytics::new(&block, receipts.as_deref());
+            Ok(Some(NewBlock { block: block.into(), analytics: Some(analytics) }))
+        }
+    }
+}
+
+/// Fetch a block, retrying transient failures
+pub async fn fetch_with_retry<M, T>(provider: &M, id: T, detail: BlockDetail) -> Result<NewBlock, NodeError>
+where
+    M: Middleware,
+    T: Into<BlockId> + Copy + Debug + Send + Sync,
+{
+    let mut attempt = 1;
+    loop {
+        match fetch_block(provider, id, detail).await {
+            Ok(Some(block)) => return Ok(block),
+            Ok(None) => return Err(NodeError::Other(format!("Block {:?} not found", id))),
+            Err(e) => {
+                if attempt >= MAX_ATTEMPTS {
+                    return Err(e);
+                }
+                warn!("Failed to fetch block {:?} (attempt {}/{}): {}", id, attempt, MAX_ATTEMPTS, e);
+                attempt += 1;
+                sleep(RETRY_DELAY).await;
+            }
+        }
+    }
+}
+
+/// Blocks replaced by a competing branch
+#[derive(Debug, Clone)]
+pub struct Reorg {
+    /// Last block shared by the old and the new branch
+    pub common_ancestor: U64,
+    /// Hashes of the blocks that left the canonical chain, oldest first
+    pub orphaned: Vec<H256>,
+}
+
+impl Reorg {
+    /// Number of orphaned blocks
+    pub fn depth(&self) -> usize {
+        self.orphaned.len()
+    }
+}
+
+/// Recent canonical blocks, contiguous and ordered by number
+#[derive(Debug, Default)]
+pub struct ChainTracker {
+    blocks: VecDeque<Block<TxHash>>,
+    /// Detail at which missing blocks are fetched
+    detail: BlockDetail,
+}
+
+impl ChainTracker {
+    pub fn new(detail: BlockDetail) -> Self {
+        ChainTracker {
+            blocks: VecDeque::new(),
+            detail,
+        }
+    }
+
+    /// Detail at which the feeds following this chain fetch blocks
+    pub fn detail(&self) -> BlockDetail {
+        self.detail
+    }
+
+    /// Hash of the canonical block at `number`, if still remembered
+    fn hash_at(&self, number: U64) -> Option<H256> {
+        let first = self.blocks.front()?.number?;
+        let index = number.checked_sub(first)?.as_usize();
+        self.blocks.get(index).and_then(|block| block.hash)
+    }
+
+    fn contains(&self, hash: H256) -> bool {
+        self.blocks.iter().any(|block| block.hash == Some(hash))
+    }
+
+    /// Number of the oldest remembered block
+    fn first_number(&self) -> Option<U64> {
+        self.blocks.front().and_then(|block| block.number)
+    }
+
+    /// Number of the newest remembered block
+    fn tip_number(&self) -> Option<U64> {
+        self.blocks.back().and_then(|block| block.number)
+    }
+
+    /// Remove every block above `number`, returning their hashes oldest first
+    fn truncate_after(&mut self, number: U64) -> Vec<H256> {
+        let keep = self.blocks.iter().take_while(|block| block.number.is_some_and(|n| n <= number)).count();
+        self.blocks.drain(keep..).filter_map(|block| block.hash).collect()
+    }
+
+    /// Append the next canonical block, forgetting the oldest beyond the tracked depth
+    pub fn push(&mut self, block: Block<TxHash>) {
+        self.blocks.push_back(block);
+        if self.blocks.len() > MAX_DEPTH {
+            self.blocks.pop_front();
+        }
+    }
+}
+
+/// Bring the tracked chain up to a new head, returning what changed in order
+///
+/// Blocks between the tracked tip and `head` are fetched by parent hash, so a feed that skipped
+/// blocks is backfilled from the same branch. Walking back until a parent matches the tracked chain
+/// also finds the fork point when `head` is on another branch, the tracked blocks above it are
+/// reported as orphaned. Heads more than [`MAX_DEPTH`] blocks past the tip are approached by
+/// number first, one block at a time, so a long gap neither loses blocks nor hides a reorg. Heads
+/// that are already known or older than the tracked chain, e.g. from a lagging endpoint, are ignored.
+pub async fn follow_head<M: Middleware>(
+    provider: &M,
+    chain: &mut ChainTracker,
+    head: NewBlock,
+) -> Result<Vec<BlockEvent>, NodeError> {
+    let Some(head_hash) = head.block.hash else {
+        return Ok(Vec::new());
+    };
+    if chain.contains(head_hash) || head.block.number.zip(chain.first_number()).is_some_and(|(n, first)| n < first) {
+        return Ok(Vec::new());
+    }
+
+    let mut events = Vec::new();
+    let head_number = head.block.number.unwrap_or_default();
+    while let Some(next) = chain.tip_number().map(|tip| tip + 1).filter(|next| head_number >= next + MAX_DEPTH) {
+        let result = match fetch_with_retry(provider, next, chain.detail).await {
+            Ok(block) => connect(provider, chain, block).await,
+            Err(e) => Err(e),
+        };
+        match result {
+            Ok(new_events) => events.extend(new_events),
+            Err(e) if events.is_empty() => return Err(e),
+            Err(e) => {
+                // Keep what was added, the next head resumes from the new tip
+                warn!("Backfill towards head {} stopped at block {}: {}", head_number, next, e);
+                return Ok(events);
+            }
+        }
+    }
+
+    match connect(provider, chain, head).await {
+        Ok(new_events) => events.extend(new_events),
+        Err(e) if events.is_empty() => return Err(e),
+        Err(e) => warn!("Failed to connect head {:?} after backfilling: {}", head_hash, e),
+    }
+    Ok(events)
+}
+
+/// Append a block to the tracked chain, walking back by parent hash until it connects
+async fn connect<M: Middleware>(
+    provider: &M,
+    chain: &mut ChainTracker,
+    head: NewBlock,
+) -> Result<Vec<BlockEvent>, NodeError> {
+    let head_hash = head.block.hash;
+
+    // Walk back from the head until the branch connects to the tracked chain
+    let mut branch = vec![head];
+    let mut connected = chain.blocks.is_empty();
+    while !connected {
+        let oldest = &branch.last().expect("branch starts with the head").block;
+        let Some(parent_number) = oldest.number.and_then(|number| number.checked_sub(U64::one())) else {
+            break;
+        };
+        if chain.hash_at(parent_number) == Some(oldest.parent_hash) {
+            connected = true;
+            break;
+        }
+        if chain.first_number().is_some_and(|first| parent_number < first) || branch.len() >= MAX_DEPTH {
+            break;
+        }
+        let parent = fetch_block(provider, oldest.parent_hash, chain.detail)
+            .await?
+            .ok_or_else(|| NodeError::Other(format!("Parent block {:?} not found", oldest.parent_hash)))?;
+        branch.push(parent);
+    }
+    branch.reverse();
+
+    let mut events = Vec::new();
+    if connected {
+        let common_ancestor = branch[0].block.number.unwrap_or_default().saturating_sub(U64::one());
+        let orphaned = chain.truncate_after(common_ancestor);
+        if !orphaned.is_empty() {
+            events.push(BlockEvent::Reorg(Reorg { common_ancestor, orphaned }));
+        }
+    } else {
+        warn!("New head {:?} does not connect to the last {} tracked blocks, resetting the chain", head_hash,
+            chain.blocks.len());
+        chain.blocks.clear();
+    }
+
+    for new_block in branch {
+        chain.push(new_block.block.clone());
+        events.push(BlockEvent::New(Box::new(new_block)));
+    }
+    Ok(events)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use async_trait::async_trait;
+    use ethers::providers::JsonRpcClient;
+    use serde::{de::DeserializeOwned, Serialize};
+    use std::collections::HashMap;
+    use std::sync::Mutex;
+
+    use crate::transport::TransportError;
+
+    /// Hash of block `number` on branch `branch`
+    fn hash(number: u64, branch: u8) -> H256 {
+        let mut hash = H256::zero();
+        hash.0[0] = branch;
+        hash.0[24..].copy_from_slice(&number.to_be_bytes());
+        hash
+    }
+
+    /// Blocks `from..=to` of `branch`, the first one building on `parent_branch`
+    fn branch(from: u64, to: u64, branch: u8, parent_branch: u8) -> Vec<Block<TxHash>> {
+        (from..=to)
+            .map(|number| Block {
+                number: Some(number.into()),
+                hash: Some(hash(number, branch)),
+                parent_hash: hash(number - 1, if number == from { parent_branch } else { branch }),
+                ..Default::default()
+            })
+            .collect()
+    }
+
+    /// Node serving blocks by hash from every branch and by number from the canonical one
+    #[derive(Debug, Default)]
+    struct ScriptedChain {
+        blocks: HashMap<H256, Block<TxHash>>,
+        canonical: HashMap<U64, H256>,
+        requests: Mutex<Vec<String>>,
+    }
+
+    impl ScriptedChain {
+        /// Serve `blocks`, the later ones replacing earlier ones of the same number as canonical
+        fn new(blocks: impl IntoIterator<Item = Block<TxHash>>) -> Self {
+            let mut chain = ScriptedChain::default();
+            for block in blocks {
+                let hash = block.hash.unwrap();
+                chain.canonical.insert(block.number.unwrap(), hash);
+                chain.blocks.insert(hash, block);
+            }
+            chain
+        }
+    }
+
+    #[async_trait]
+    impl JsonRpcClient for ScriptedChain {
+        type Error = TransportError;
+
+        async fn request<T, R>(&self, method: &str, params: T) -> Result<R, TransportError>
+        where
+            T: Debug + Serialize + Send + Sync,
+            R: DeserializeOwned + Send,
+        {
+            let params = serde_json::to_value(params)?;
+            self.requests.lock().unwrap().push(method.to_string());
+            let hash = match method {
+                "eth_getBlockByHash" => Some(serde_json::from_value(params[0].clone())?),
+                "eth_getBlockByNumber" => self.canonical.get(&serde_json::from_value(params[0].clone())?).copied(),
+                _ => None,
+            };
+            let block = hash.and_then(|hash| self.blocks.get(&hash));
+            Ok(serde_json::from_value(serde_json::to_value(block)?)?)
+        }
+    }
+
+    fn tracked(blocks: Vec<Block<TxHash>>) -> ChainTracker {
+        let mut chain = ChainTracker::new(BlockDetail::Hashes);
+        for block in blocks {
+            chain.push(block);
+        }
+        chain
+    }
+
+    fn head(block: &Block<TxHash>) -> NewBlock {
+        NewBlock { block: block.clone(), analytics: None }
+    }
+
+    /// Hashes of the new blocks in `events`, in order
+    fn new_blocks(events: &[BlockEvent]) -> Vec<H256> {
+        events
+            .iter()
+            .filter_map(|event| match event {
+                BlockEvent::New(new_block) => new_block.block.hash,
+                BlockEvent::Reorg(_) => None,
+            })
+            .collect()
+    }
+
+    fn reorgs(events: &[BlockEvent]) -> Vec<&Reorg> {
+        events
+            .iter()
+            .filter_map(|event| match event {
+                BlockEvent::Reorg(reorg) => Some(reorg),
+                BlockEvent::New(_) => None,
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn backfills_skipped_blocks() {
+        let blocks = branch(1, 6, 0, 0);
+        let provider = Provider::new(ScriptedChain::new(blocks.clone()));
+        let mut chain = tracked(blocks[..3].to_vec());
+
+        let events = follow_head(&provider, &mut chain, head(&blocks[5])).await.unwrap();
+
+        assert_eq!(new_blocks(&events), vec![hash(4, 0), hash(5, 0), hash(6, 0)]);
+        assert!(reorgs(&events).is_empty());
+        assert_eq!(chain.tip_number(), Some(U64::from(6)));
+    }
+
+    #[tokio::test]
+    async fn reports_a_depth_two_reorg() {
+        let old = branch(1, 5, 0, 0);
+        let new = branch(4, 6, 1, 0);
+        let provider = Provider::new(ScriptedChain::new(old.iter().chain(&new).cloned()));
+        let mut chain = tracked(old);
+
+        let events = follow_head(&provider, &mut chain, head(&new[2])).await.unwrap();
+
+        let reorgs = reorgs(&events);
+        assert_eq!(reorgs.len(), 1);
+        assert_eq!(reorgs[0].common_ancestor, U64::from(3));
+        assert_eq!(reorgs[0].orphaned, vec![hash(4, 0), hash(5, 0)]);
+        assert!(matches!(events[0], BlockEvent::Reorg(_)), "the reorg comes before the new branch");
+        assert_eq!(new_blocks(&events), vec![hash(4, 1), hash(5, 1), hash(6, 1)]);
+        assert_eq!(chain.hash_at(U64::from(3)), Some(hash(3, 0)));
+        assert_eq!(chain.hash_at(U64::from(5)), Some(hash(5, 1)));
+    }
+
+    #[tokio::test]
+    async fn ignores_a_lagging_head() {
+        let blocks = branch(1, 10, 0, 0);
+        let provider = Provider::new(ScriptedChain::new(blocks.clone()));
+        let mut chain = tracked(blocks[4..].to_vec());
+
+        // An endpoint behind the others reports a block we already have, or one older than the chain
+        for lagging in [&blocks[7], &blocks[2]] {
+            let events = follow_head(&provider, &mut chain, head(lagging)).await.unwrap();
+            assert!(events.is_empty());
+        }
+
+        assert!(provider.as_ref().requests.lock().unwrap().is_empty());
+        assert_eq!(chain.tip_number(), Some(U64::from(10)));
+    }
+
+    #[tokio::test]
+    async fn backfills_a_gap_deeper_than_the_tracked_chain() {
+        let last = 3 + MAX_DEPTH as u64 + 10;
+        let blocks = branch(1, last, 0, 0);
+        let provider = Provider::new(ScriptedChain::new(blocks.clone()));
+        let mut chain = tracked(blocks[..3].to_vec());
+
+        let events = follow_head(&provider, &mut chain, head(blocks.last().unwrap())).await.unwrap();
+
+        let expected: Vec<H256> = (4..=last).map(|number| hash(number, 0)).collect();
+        assert_eq!(new_blocks(&events), expected);
+        assert!(reorgs(&events).is_empty());
+        assert_eq!(chain.tip_number(), Some(U64::from(last)));
+    }
+
+    #[tokio::test]
+    async fn reports_a_reorg_hidden_in_a_deep_gap() {
+        let last = 5 + MAX_DEPTH as u64 + 10;
+        let old = branch(1, 5, 0, 0);
+        let new = branch(4, last, 1, 0);
+        let provider = Provider::new(ScriptedChain::new(old.iter().chain(&new).cloned()));
+        let mut chain = tracked(old);
+
+        let events = follow_head(&provider, &mut chain, head(new.last().unwrap())).await.unwrap();
+
+        let reorgs = reorgs(&events);
+        assert_eq!(reorgs.len(), 1);
+        assert_eq!(reorgs[0].common_ancestor, U64::from(3));
+        assert_eq!(reorgs[0].orphaned, vec![hash(4, 0), hash(5, 0)]);
+        let expected: Vec<H256> = (4..=last).map(|number| hash(number, 1)).collect();
+        assert_eq!(new_blocks(&events), expected);
+    }
+}
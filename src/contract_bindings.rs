@@ -1,6 +1,8 @@
 use ethers::prelude::*;
 use std::sync::Arc;
 
+use crate::error::NodeError;
+
 // This module provides bindings to interact with the UltrethContract
 // In a production environment, these would be generated using ethers-rs abigen macro
 
@@ -37,14 +39,26 @@ mod empty_contract {
 #[cfg(solidity_disabled)]
 pub use empty_contract::UltrethContract;
 
-/// Deploy the UltrethContract to the network
-pub async fn deploy_contract(
-    client: Arc<Provider<Http>>,
+/// Classify a contract call failure by the error of the underlying middleware
+#[cfg(not(solidity_disabled))]
+fn contract_error<M: Middleware>(error: ContractError<M>) -> NodeError {
+    if let Some(e) = error.as_middleware_error() {
+        NodeError::from_middleware(e)
+    } else if let Some(e) = error.as_provider_error() {
+        NodeError::from(e)
+    } else {
+        NodeError::Other(error.to_string())
+    }
+}
+
+/// Deploy the UltrethContract to the network through any middleware stack
+pub async fn deploy_contract<M: Middleware + 'static>(
+    client: Arc<M>,
     wallet: LocalWallet,
-) -> Result<UltrethContract<SignerMiddleware<Provider<Http>, LocalWallet>>, Box<dyn std::error::Error>> {
+) -> Result<UltrethContract<SignerMiddleware<Arc<M>, LocalWallet>>, NodeError> {
     #[cfg(solidity_disabled)]
     {
-        return Err("Solidity contract integration is disabled. Rebuild with --features solidity to enable.".into());
+        return Err(NodeError::Other("Solidity contract integration is disabled. Rebuild with --features solidity to enable.".to_string()));
     }
     
     #[cfg(not(solidity_disabled))]
@@ -54,40 +68,43 @@ pub async fn deploy_contract(
         let client = Arc::new(client);
         
         // Deploy the contract
-        let contract = UltrethContract::deploy(client, ())?
+        let contract = UltrethContract::deploy(client, ())
+            .map_err(contract_error)?
             .send()
-            .await?;
+            .await
+            .map_err(contract_error)?;
         
         Ok(contract)
     }
 }
 
 /// Connect to an existing UltrethContract
-pub fn connect_to_contract(
-    client: Arc<SignerMiddleware<Provider<Http>, LocalWallet>>,
+pub fn connect_to_contract<M: Middleware>(
+    client: Arc<M>,
     address: Address,
-) -> UltrethContract<SignerMiddleware<Provider<Http>, LocalWallet>> {
+) -> UltrethContract<M> {
     UltrethContract::new(address, client)
 }
 
 /// Record a transaction in the contract
-pub async fn record_transaction(
-    contract: &UltrethContract<SignerMiddleware<Provider<Http>, LocalWallet>>,
+pub async fn record_transaction<M: Middleware + 'static>(
+    contract: &UltrethContract<M>,
     from: Address,
     to: Address,
     value: U256,
     data: Bytes,
-) -> Result<TransactionReceipt, Box<dyn std::error::Error>> {
+) -> Result<TransactionReceipt, NodeError> {
     #[cfg(solidity_disabled)]
     {
-        return Err("Solidity contract integration is disabled. Rebuild with --features solidity to enable.".into());
+        return Err(NodeError::Other("Solidity contract integration is disabled. Rebuild with --features solidity to enable.".to_string()));
     }
     
     #[cfg(not(solidity_disabled))]
     {
         let tx = contract.record_transaction(from, to, value, data)
             .send()
-            .await?
+            .await
+            .map_err(contract_error)?
             .await?;
         
         Ok(tx.unwrap())
@@ -95,22 +112,23 @@ pub async fn record_transaction(
 }
 
 /// Record a block in the contract
-pub async fn record_block(
-    contract: &UltrethContract<SignerMiddleware<Provider<Http>, LocalWallet>>,
+pub async fn record_block<M: Middleware + 'static>(
+    contract: &UltrethContract<M>,
     block_number: U256,
     block_hash: H256,
     timestamp: U256,
-) -> Result<TransactionReceipt, Box<dyn std::error::Error>> {
+) -> Result<TransactionReceipt, NodeError> {
     #[cfg(solidity_disabled)]
     {
-        return Err("Solidity contract integration is disabled. Rebuild with --features solidity to enable.".into());
+        return Err(NodeError::Other("Solidity contract integration is disabled. Rebuild with --features solidity to enable.".to_string()));
     }
     
     #[cfg(not(solidity_disabled))]
     {
         let tx = contract.record_block(block_number, block_hash, timestamp)
             .send()
-            .await?
+            .await
+            .map_err(contract_error)?
             .await?;
         
         Ok(tx.unwrap())
@@ -118,20 +136,21 @@ pub async fn record_block(
 }
 
 /// Update the query rate in the contract
-pub async fn update_query_rate(
-    contract: &UltrethContract<SignerMiddleware<Provider<Http>, LocalWallet>>,
+pub async fn update_query_rate<M: Middleware + 'static>(
+    contract: &UltrethContract<M>,
     rate: U256,
-) -> Result<TransactionReceipt, Box<dyn std::error::Error>> {
+) -> Result<TransactionReceipt, NodeError> {
     #[cfg(solidity_disabled)]
     {
-        return Err("Solidity contract integration is disabled. Rebuild with --features solidity to enable.".into());
+        return Err(NodeError::Other("Solidity contract integration is disabled. Rebuild with --features solidity to enable.".to_string()));
     }
     
     #[cfg(not(solidity_disabled))]
     {
         let tx = contract.update_query_rate(rate)
             .send()
-            .await?
+            .await
+            .map_err(contract_error)?
             .await?;
         
         Ok(tx.unwrap())
@@ -139,17 +158,17 @@ pub async fn update_query_rate(
 }
 
 /// Get statistics from the contract
-pub async fn get_statistics(
-    contract: &UltrethContract<SignerMiddleware<Provider<Http>, LocalWallet>>,
-) -> Result<Vec<U256>, Box<dyn std::error::Error>> {
+pub async fn get_statistics<M: Middleware + 'static>(
+    contract: &UltrethContract<M>,
+) -> Result<Vec<U256>, NodeError> {
     #[cfg(solidity_disabled)]
     {
-        return Err("Solidity contract integration is disabled. Rebuild with --features solidity to enable.".into());
+        return Err(NodeError::Other("Solidity contract integration is disabled. Rebuild with --features solidity to enable.".to_string()));
     }
     
     #[cfg(not(solidity_disabled))]
     {
-        let stats = contract.get_statistics().call().await?;
+        let stats = contract.get_statistics().call().await.map_err(contract_error)?;
         Ok(stats)
     }
 }
\ No newline at end of file
@@ -1,17 +1,93 @@
 use colored::*;
 use ethers::prelude::*;
-use std::str::FromStr;
+use std::collections::HashSet;
+
+use crate::accesslist::{unrelated_addresses, AccessListSize};
+use crate::analytics::{effective_tip, BlockAnalytics};
+use crate::authorization::{authorization_list, Authorization};
+use crate::blob::BlobFields;
+use crate::mempool::{Replacement, ReplacementKind, MIN_PRICE_BUMP};
+use crate::token::{token_amount, TokenCache};
+use crate::txpool::{PendingTx, PoolStatus};
+use crate::units::{format_amount, format_token_amount, format_units, Unit};
+
+/// Settings of the transaction display taken from the command line
+#[derive(Debug, Clone, Default)]
+pub struct DisplayOptions {
+    /// Delegation targets considered trusted, authorizations to other addresses are flagged
+    pub known_delegates: HashSet<Address>,
+    /// Fraction digits amounts are rounded to, exact if `None`
+    pub max_fraction_digits: Option<u32>,
+}
+
+/// Fee market state of the latest block, used to judge whether transactions are includable
+#[derive(Debug, Clone, Copy, Default)]
+pub struct FeeContext {
+    pub base_fee: Option<U256>,
+    pub blob_base_fee: Option<U256>,
+}
+
+impl FeeContext {
+    /// Take the base fees of a new block, keeping the previous ones for fees it lacks
+    pub fn update(&mut self, block: &Block<TxHash>, blob_base_fee: Option<U256>) {
+        self.base_fee = block.base_fee_per_gas.or(self.base_fee);
+        self.blob_base_fee = blob_base_fee.or(self.blob_base_fee);
+    }
+}
 
 /// Format a transaction for display in the terminal
-pub async fn format_transaction(
-    tx: &Transaction,
-    provider: &Provider<Http>,
+///
+/// `replacement` describes the earlier transaction with the same sender and nonce, if any.
+/// `fees` holds the base fees of the latest block, used to show the effective tip. Amounts of token
+/// transfers and approvals are shown in units of the token, its metadata is looked up in `tokens`.
+pub async fn format_transaction<M: Middleware>(
+    pending: &PendingTx,
+    replacement: Option<&Replacement>,
+    fees: &FeeContext,
+    options: &DisplayOptions,
+    tokens: &TokenCache,
+    provider: &M,
 ) -> String {
+    let tx = &pending.tx;
+    let digits = options.max_fraction_digits;
     let mut output = String::new();
     
     // Transaction header
     output.push_str(&format!("{} {}\n", "Transaction:".bright_yellow().bold(), tx.hash));
     
+    // Pool tag, plain pending transactions are not tagged
+    match &pending.status {
+        PoolStatus::Pending => {}
+        PoolStatus::Queued(reason) => {
+            output.push_str(&format!("{} {} ({})\n", "Pool:".cyan(), "QUEUED".bright_red().bold(), reason));
+        }
+        PoolStatus::Promoted { queued_for } => {
+            output.push_str(&format!("{} {} (queued for {:.1}s)\n", "Pool:".cyan(),
+                "PROMOTED".bright_green().bold(), queued_for.as_secs_f64()));
+        }
+    }
+    
+    // Speed-ups, cancellations and other replacements of an earlier transaction
+    if let Some(replacement) = replacement {
+        let kind = match replacement.kind {
+            ReplacementKind::SpeedUp => replacement.kind.to_string().bright_green().bold(),
+            ReplacementKind::Cancel => replacement.kind.to_string().bright_red().bold(),
+            ReplacementKind::Replacement => replacement.kind.to_string().bright_magenta().bold(),
+        };
+        output.push_str(&format!("{} {} of {}\n", "Replaces:".cyan(), kind, replacement.replaced.hash));
+        if let Some(fee_bump) = replacement.fee_bump {
+            let label = if tx.max_fee_per_gas.is_some() { "max fee" } else { "gas price" };
+            output.push_str(&format!("{} {:+.2}% ({})", "Fee Bump:".cyan(), fee_bump, label));
+            if let Some(tip_bump) = replacement.tip_bump {
+                output.push_str(&format!(", {:+.2}% (priority fee)", tip_bump));
+            }
+            if replacement.below_min_bump {
+                output.push_str(&format!(" {}", format!("below the {}% minimum bump", MIN_PRICE_BUMP).yellow()));
+            }
+            output.push('\n');
+        }
+    }
+    
     // Basic transaction info
     output.push_str(&format!("{} {}\n", "From:".cyan(), tx.from));
     
@@ -22,10 +98,20 @@ pub async fn format_transaction(
     }
     
     // Value and gas information
-    let value_eth = format_eth(tx.value);
+    let value_eth = format_eth(tx.value, digits);
     output.push_str(&format!("{} {} ETH\n", "Value:".cyan(), value_eth));
-    output.push_str(&format!("{} {}\n", "Gas Price:".cyan(), format_gwei(tx.gas_price.unwrap_or_default())));
+    output.push_str(&format_fees(tx, fees.base_fee, digits));
     output.push_str(&format!("{} {}\n", "Gas Limit:".cyan(), tx.gas));
+    if let Some(blob) = BlobFields::from_transaction(tx) {
+        output.push_str(&format_blob_fields(&blob, fees.blob_base_fee, digits));
+    }
+    if let Some(authorizations) = authorization_list(tx) {
+        output.push_str(&format!("{} {}\n", "Authorizations:".cyan(), authorizations.len()));
+        for authorization in &authorizations {
+            output.push_str(&format_authorization(authorization, &options.known_delegates, provider).await);
+        }
+    }
+    output.push_str(&format_access_list(tx));
     
     // Transaction type and other details
     if let Some(tx_type) = tx.transaction_type {
@@ -39,11 +125,19 @@ pub async fn format_transaction(
     // Input data
     // Input is not an Option type in Transaction
     let input = &tx.input;
-        if input.0.len() > 0 {
+        if !input.0.is_empty() {
             // Try to decode function signature
             let func_sig = decode_function_signature(input);
             output.push_str(&format!("{} {}\n", "Function:".cyan(), func_sig));
             
+            // Token amounts are meaningless without the token's decimals
+            if let (Some(to), Some(amount)) = (tx.to, token_amount(input)) {
+                if let Some(token) = tokens.get(provider, to).await {
+                    output.push_str(&format!("{} {}\n", "Token Amount:".cyan(),
+                        format_token_amount(amount, token.decimals, &token.symbol, digits)));
+                }
+            }
+            
             // Show input data (truncated if too long)
             let input_str = format!("{}", input);
             let truncated = if input_str.len() > 100 {
@@ -69,29 +163,152 @@ pub async fn format_transaction(
     output
 }
 
-/// Format ETH value with proper decimal places
-fn format_eth(wei: U256) -> String {
-    let wei_str = wei.to_string();
-    let eth = wei_f64(wei);
-    
-    if eth < 0.000001 && eth > 0.0 {
-        format!("{} ({})", eth, wei_str)
+/// Number of access list entries listed per transaction, the rest are only counted
+const MAX_ACCESS_LIST_ENTRIES: usize = 5;
+
+/// Format the fee fields of a transaction according to its type
+///
+/// Dynamic fee transactions show their fee caps, legacy ones their gas price. With a known base fee
+/// the effective tip is shown and transactions that cannot be included at it are marked.
+fn format_fees(tx: &Transaction, base_fee: Option<U256>, digits: Option<u32>) -> String {
+    let mut output = String::new();
+    let fee_cap = match (tx.max_fee_per_gas, tx.max_priority_fee_per_gas) {
+        (Some(max_fee), Some(max_priority_fee)) => {
+            output.push_str(&format!("{} {}\n", "Max Fee:".cyan(), format_gwei(max_fee, digits)));
+            output.push_str(&format!("{} {}\n", "Max Priority Fee:".cyan(), format_gwei(max_priority_fee, digits)));
+            max_fee
+        }
+        _ => {
+            let gas_price = tx.gas_price.unwrap_or_default();
+            output.push_str(&format!("{} {}\n", "Gas Price:".cyan(), format_gwei(gas_price, digits)));
+            gas_price
+        }
+    };
+
+    if let Some(base_fee) = base_fee {
+        output.push_str(&format!("{} {} at base fee {}", "Effective Tip:".cyan(),
+            format_gwei(effective_tip(tx, base_fee), digits), format_gwei(base_fee, digits)));
+        if fee_cap < base_fee {
+            output.push_str(&format!(" {} (fee cap {} below base fee)", "UNDERPRICED".bright_red().bold(),
+                format_gwei(base_fee - fee_cap, digits)));
+        }
+        output.push('\n');
+    }
+    output
+}
+
+/// Format the blobs of a type 3 transaction and its blob fee cap
+fn format_blob_fields(blob: &BlobFields, blob_base_fee: Option<U256>, digits: Option<u32>) -> String {
+    let mut output = format!("{} {} ({} blob gas)\n", "Blobs:".cyan(), blob.versioned_hashes.len(), blob.blob_gas());
+    if let Some(max_fee) = blob.max_fee_per_blob_gas {
+        output.push_str(&format!("{} {}", "Max Fee Per Blob Gas:".cyan(), format_gwei(max_fee, digits)));
+        if let Some(blob_base_fee) = blob_base_fee {
+            output.push_str(&format!(" at blob base fee {}", format_gwei(blob_base_fee, digits)));
+            if max_fee < blob_base_fee {
+                output.push_str(&format!(" {}", "UNDERPRICED".bright_red().bold()));
+            }
+        }
+        output.push('\n');
+    }
+    for hash in &blob.versioned_hashes {
+        output.push_str(&format!("  {} {:?}\n", "Versioned Hash:".cyan(), hash));
+    }
+    output
+}
+
+/// Format the access list of a transaction compactly, one line per address with its storage key count
+///
+/// Addresses the transaction does not obviously interact with are listed in a warning.
+fn format_access_list(tx: &Transaction) -> String {
+    let (Some(access_list), Some(size)) = (&tx.access_list, AccessListSize::of(tx)) else {
+        return String::new();
+    };
+    let mut output = format!("{} {} addresses, {} storage keys\n", "Access List:".cyan(), size.addresses,
+        size.storage_keys);
+    for item in access_list.0.iter().take(MAX_ACCESS_LIST_ENTRIES) {
+        output.push_str(&format!("  {:?} ({} keys)\n", item.address, item.storage_keys.len()));
+    }
+    if size.addresses > MAX_ACCESS_LIST_ENTRIES {
+        output.push_str(&format!("  ... {} more addresses\n", size.addresses - MAX_ACCESS_LIST_ENTRIES));
+    }
+    for address in unrelated_addresses(tx) {
+        output.push_str(&format!("{} {:?} is not referenced by the transaction\n",
+            "Access List Warning:".bright_yellow().bold(), address));
+    }
+    output
+}
+
+/// Format one authorization of a type 4 transaction
+///
+/// Delegations to an address outside `known_delegates` are marked, as are delegates without code.
+async fn format_authorization<M: Middleware>(
+    authorization: &Authorization,
+    known_delegates: &HashSet<Address>,
+    provider: &M,
+) -> String {
+    let authority = authorization.authority()
+        .map_or_else(|| "INVALID SIGNATURE".bright_red().bold().to_string(), |authority| format!("{:?}", authority));
+    let chain = if authorization.chain_id.is_zero() { "any".to_string() } else { authorization.chain_id.to_string() };
+    let mut output = format!("  {} {}", "Authority:".cyan(), authority);
+
+    if authorization.is_revocation() {
+        output.push_str(&format!(" {}", "clears its delegation".bright_green()));
     } else {
-        format!("{}", eth)
+        output.push_str(&format!(" delegates to {:?}", authorization.address));
+        if !known_delegates.contains(&authorization.address) {
+            output.push_str(&format!(" {}", "UNKNOWN DELEGATE".bright_red().bold()));
+            if let Ok(code) = provider.get_code(authorization.address, None).await {
+                if code.0.is_empty() {
+                    output.push_str(" (no code)");
+                }
+            }
+        }
     }
+    output.push_str(&format!(" (chain {}, nonce {})\n", chain, authorization.nonce));
+    output
 }
 
-/// Format gas price in Gwei
-fn format_gwei(wei: U256) -> String {
-    let gwei = wei_f64(wei) * 1e-9;
-    format!("{} Gwei", gwei)
+/// Format the fee analytics of a block, one line per metric, amounts rounded to `digits` fraction digits
+pub fn format_block_analytics(analytics: &BlockAnalytics, digits: Option<u32>) -> String {
+    let mut output = String::new();
+    let types = &analytics.tx_types;
+    output.push_str(&format!("{} {} legacy, {} access list, {} EIP-1559, {} blob, {} set-code, {} other\n",
+        "Tx Types:".cyan(), types.legacy, types.access_list, types.dynamic_fee, types.blob, types.set_code, types.other));
+    output.push_str(&format!("{} {} ETH\n", "Burned:".cyan(), format_eth(analytics.burned, digits)));
+    output.push_str(&format!("{} {} ETH", "Priority Fees:".cyan(), format_eth(analytics.priority_fees, digits)));
+    if analytics.gas_estimated {
+        output.push_str(" (upper bound, receipts unavailable)");
+    }
+    output.push('\n');
+    if let Some(tip) = analytics.median_tip {
+        output.push_str(&format!("{} {}\n", "Median Tip:".cyan(), format_gwei(tip, digits)));
+    }
+    if !analytics.top_consumers.is_empty() {
+        output.push_str(&format!("{}\n", "Top Gas Consumers:".cyan()));
+        for consumer in &analytics.top_consumers {
+            let to = consumer.to.map_or_else(|| "Contract Creation".to_string(), |to| format!("{:?}", to));
+            output.push_str(&format!("  {} {} gas ({} txs)\n", to, consumer.gas_used, consumer.transactions));
+        }
+    }
+    output
+}
+
+/// Amounts below a millionth of an ether are also shown in wei
+const SMALL_ETH_WEI: u64 = 1_000_000_000_000;
+
+/// Format an amount of wei in ETH without the unit, exactly or rounded to `digits` fraction digits
+pub fn format_eth(wei: U256, digits: Option<u32>) -> String {
+    let eth = format_units(wei, Unit::Ether.decimals(), digits);
+    if !wei.is_zero() && wei < U256::from(SMALL_ETH_WEI) {
+        format!("{} ({})", eth, format_amount(wei, Unit::Wei, None))
+    } else {
+        eth
+    }
 }
 
-/// Convert U256 to f64 for display purposes
-fn wei_f64(wei: U256) -> f64 {
-    let wei_str = wei.to_string();
-    let wei_f64 = f64::from_str(&wei_str).unwrap_or(0.0);
-    wei_f64 * 1e-18
+/// Format an amount of wei in Gwei, exactly or rounded to `digits` fraction digits
+pub fn format_gwei(wei: U256, digits: Option<u32>) -> String {
+    format_amount(wei, Unit::Gwei, digits)
 }
 
 /// Attempt to decode the function signature from transaction input
@@ -139,7 +356,7 @@ pub fn format_block(block: &Block<TxHash>) -> String {
     output.push_str(&format!("{} {}\n", "Gas Used:".cyan(), block.gas_used));
     output.push_str(&format!("{} {}\n", "Gas Limit:".cyan(), block.gas_limit));
     output.push_str(&format!("{} {}\n", "Base Fee:".cyan(), 
-        block.base_fee_per_gas.map_or("N/A".to_string(), |fee| format_gwei(fee))));
+        block.base_fee_per_gas.map_or("N/A".to_string(), |base_fee| format_gwei(base_fee, None))));
     
     // Transaction count
     output.push_str(&format!("{} {}\n", "Transactions:".cyan(), block.transactions.len()));
@@ -148,4 +365,34 @@ pub fn format_block(block: &Block<TxHash>) -> String {
     output.push_str(&format!("{}", "----------------------------------------".bright_blue()));
     
     output
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn gwei(amount: u64) -> U256 {
+        U256::from(amount) * U256::exp10(9)
+    }
+
+    #[test]
+    fn shows_fees_by_transaction_type() {
+        let dynamic_fee = Transaction {
+            transaction_type: Some(2.into()),
+            max_fee_per_gas: Some(gwei(30)),
+            max_priority_fee_per_gas: Some(gwei(2)),
+            gas_price: Some(gwei(30)),
+            ..Default::default()
+        };
+        let output = format_fees(&dynamic_fee, Some(gwei(10)), None);
+        assert!(output.contains("Max Fee:") && output.contains("Max Priority Fee:") && !output.contains("Gas Price:"));
+        assert!(output.contains("Effective Tip:") && !output.contains("UNDERPRICED"));
+
+        let legacy = Transaction { gas_price: Some(gwei(8)), ..Default::default() };
+        let output = format_fees(&legacy, Some(gwei(10)), None);
+        assert!(output.contains("Gas Price:") && !output.contains("Max Fee:"));
+        assert!(output.contains("UNDERPRICED"));
+
+        // Without a block yet there is no base fee to judge the fees against
+        assert!(!format_fees(&legacy, None, None).contains("Effective Tip:"));
+    }
+}
@@ -0,0 +1,205 @@
+use ethers::providers::{HttpClientError, IpcError, JsonRpcError, MiddlewareError, ProviderError, RpcError};
+use std::io;
+use thiserror::Error;
+
+use crate::transport::TransportError;
+
+/// JSON-RPC codes used by providers to signal throttling (Cannot fulfill request, Limit exceeded)
+const RATE_LIMIT_CODES: [i64; 2] = [-32046, -32005];
+
+/// JSON-RPC code for a method the endpoint does not expose
+const METHOD_NOT_FOUND: i64 = -32601;
+
+/// HTTP status returned by throttling gateways
+const TOO_MANY_REQUESTS: i64 = 429;
+
+/// Errors raised while talking to Ethereum nodes, classified from the JSON-RPC error code or the transport failure
+#[derive(Debug, Error)]
+pub enum NodeError {
+    /// Throttling response (-32046, -32005 or HTTP 429)
+    #[error("Rate limited (code: {code}): {message}")]
+    RateLimited { code: i64, message: String },
+    /// The endpoint does not expose the method (-32601)
+    #[error("Method not found (code: -32601): {0}")]
+    MethodNotFound(String),
+    /// Missing or invalid API key (HTTP 401/403)
+    #[error("Unauthorized: {0}")]
+    Unauthorized(String),
+    #[error("Connection timeout: {0}")]
+    Timeout(String),
+    #[error("Invalid endpoint URL: {0}")]
+    InvalidUrl(String),
+    /// The node has not caught up with the chain and cannot serve the request
+    #[error("Node is syncing: {0}")]
+    Syncing(String),
+    #[error("Failed to decode response: {0}")]
+    Decode(String),
+    /// Any other JSON-RPC error response, the request itself was rejected
+    #[error("JSON-RPC error (code: {code}): {message}")]
+    Rpc { code: i64, message: String },
+    /// Connection failures and other transport errors
+    #[error("Transport error: {0}")]
+    Transport(String),
+    #[error("{0}")]
+    Other(String),
+}
+
+impl NodeError {
+    /// Classify a JSON-RPC error response by its code
+    pub fn from_response(response: &JsonRpcError) -> Self {
+        let message = response.message.clone();
+        match response.code {
+            code if RATE_LIMIT_CODES.contains(&code) => NodeError::RateLimited { code, message },
+            METHOD_NOT_FOUND => NodeError::MethodNotFound(message),
+            code => {
+                // Nodes report these with the generic server error code, only the message tells them apart
+                let lower = message.to_lowercase();
+                if lower.contains("syncing") {
+                    NodeError::Syncing(message)
+                } else if lower.contains("unauthorized") || lower.contains("api key") || lower.contains("project id") {
+                    NodeError::Unauthorized(message)
+                } else {
+                    NodeError::Rpc { code, message }
+                }
+            }
+        }
+    }
+
+    /// Classify any JSON-RPC client error from its error response or decoding failure
+    pub fn from_rpc_error<E: RpcError + ?Sized>(error: &E) -> Self {
+        if let Some(response) = error.as_error_response() {
+            NodeError::from_response(response)
+        } else if let Some(e) = error.as_serde_error() {
+            NodeError::Decode(e.to_string())
+        } else {
+            NodeError::Transport(error.to_string())
+        }
+    }
+
+    /// Classify the error of an arbitrary middleware stack
+    pub fn from_middleware<E: MiddlewareError>(error: &E) -> Self {
+        if let Some(provider_error) = error.as_provider_error() {
+            NodeError::from(provider_error)
+        } else if let Some(response) = error.as_error_response() {
+            NodeError::from_response(response)
+        } else {
+            NodeError::Other(error.to_string())
+        }
+    }
+
+    /// Whether the endpoint is throttling us
+    pub fn is_rate_limited(&self) -> bool {
+        matches!(self, NodeError::RateLimited { .. })
+    }
+
+    /// Whether the error should slow down the request rate (throttling or timeouts)
+    pub fn is_throttling(&self) -> bool {
+        matches!(self, NodeError::RateLimited { .. } | NodeError::Timeout(_))
+    }
+
+    /// Whether the error reflects a problem with the endpoint rather than with the request
+    ///
+    /// Another endpoint may expose a missing method, be synced or accept our credentials.
+    pub fn is_endpoint_failure(&self) -> bool {
+        !matches!(self, NodeError::Rpc { .. } | NodeError::Other(_))
+    }
+}
+
+impl From<&TransportError> for NodeError {
+    fn from(error: &TransportError) -> Self {
+        match error {
+            TransportError::Http(HttpClientError::ReqwestError(e)) => {
+                if e.is_timeout() {
+                    return NodeError::Timeout(e.to_string());
+                }
+                match e.status().map(|status| status.as_u16()) {
+                    Some(429) => NodeError::RateLimited { code: TOO_MANY_REQUESTS, message: e.to_string() },
+                    Some(401 | 403) => NodeError::Unauthorized(e.to_string()),
+                    _ => NodeError::Transport(e.to_string()),
+                }
+            }
+            TransportError::Http(HttpClientError::SerdeJson { err, text }) => {
+                NodeError::Decode(format!("{} (response: {})", err, text.trim()))
+            }
+            TransportError::Ipc(IpcError::IoError(e)) if e.kind() == io::ErrorKind::TimedOut => {
+                NodeError::Timeout(e.to_string())
+            }
+            TransportError::Decode(e) => NodeError::Decode(e.to_string()),
+            TransportError::Timeout(_) => NodeError::Timeout(error.to_string()),
+            TransportError::PubsubUnsupported => NodeError::Other(error.to_string()),
+            TransportError::Http(_) | TransportError::Ws(_) | TransportError::Ipc(_) => NodeError::from_rpc_error(error),
+        }
+    }
+}
+
+impl From<TransportError> for NodeError {
+    fn from(error: TransportError) -> Self {
+        NodeError::from(&error)
+    }
+}
+
+impl From<&ProviderError> for NodeError {
+    fn from(error: &ProviderError) -> Self {
+        match error {
+            ProviderError::JsonRpcClientError(inner) => {
+                let inner: &(dyn std::error::Error + Send + Sync + 'static) = inner.as_ref();
+                match inner.downcast_ref::<TransportError>() {
+                    Some(transport_error) => NodeError::from(transport_error),
+                    None => NodeError::from_rpc_error(error),
+                }
+            }
+            ProviderError::HTTPError(e) if e.is_timeout() => NodeError::Timeout(e.to_string()),
+            ProviderError::HTTPError(e) => NodeError::Transport(e.to_string()),
+            ProviderError::SerdeJson(e) => NodeError::Decode(e.to_string()),
+            _ => NodeError::Other(error.to_string()),
+        }
+    }
+}
+
+impl From<ProviderError> for NodeError {
+    fn from(error: ProviderError) -> Self {
+        NodeError::from(&error)
+    }
+}
+
+impl From<url::ParseError> for NodeError {
+    fn from(error: url::ParseError) -> Self {
+        NodeError::InvalidUrl(error.to_string())
+    }
+}
+
+impl From<serde_json::Error> for NodeError {
+    fn from(error: serde_json::Error) -> Self {
+        NodeError::Decode(error.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn response(code: i64, message: &str) -> JsonRpcError {
+        JsonRpcError { code, message: message.to_string(), data: None }
+    }
+
+    #[test]
+    fn missing_header_is_a_request_error() {
+        let error = NodeError::from_response(&response(-32000, "header not found"));
+        assert!(matches!(error, NodeError::Rpc { code: -32000, .. }));
+        assert!(!error.is_endpoint_failure());
+    }
+
+    #[test]
+    fn classifies_response_codes() {
+        assert!(NodeError::from_response(&response(-32005, "limit exceeded")).is_rate_limited());
+        assert!(matches!(NodeError::from_response(&response(-32601, "no such method")), NodeError::MethodNotFound(_)));
+        assert!(NodeError::from_response(&response(-32000, "node is syncing")).is_endpoint_failure());
+    }
+
+    #[test]
+    fn request_timeouts_throttle_and_fail_over() {
+        let error = NodeError::from(TransportError::Timeout("eth_call".to_string()));
+        assert!(matches!(error, NodeError::Timeout(_)));
+        assert!(error.is_throttling() && error.is_endpoint_failure());
+    }
+}
@@ -1,37 +1,51 @@
 use ethers::prelude::*;
 use log::{error, info, warn};
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 use std::time::{Duration, Instant};
 use tokio::sync::mpsc;
 use tokio::time::sleep;
 
-/// Helper function to extract error code from Ethereum JSON-RPC errors
-fn get_error_code(error: &impl std::fmt::Display) -> Option<i32> {
-    let error_str = error.to_string();
-    
-    // Look for error code pattern in the error message
-    if let Some(code_start) = error_str.find("code: -") {
-        let code_substr = &error_str[code_start + 6..];
-        if let Some(code_end) = code_substr.find(',') {
-            let code_str = &code_substr[..code_end];
-            return code_str.parse::<i32>().ok();
-        } else if let Some(code_end) = code_substr.find(')') {
-            let code_str = &code_substr[..code_end];
-            return code_str.parse::<i32>().ok();
-        }
-    }
-    
-    None
+use crate::batch::BatchConfig;
+use crate::chain::{fetch_block, fetch_with_retry, follow_head, BlockEvent, ChainTracker, NewBlock};
+use crate::error::NodeError;
+use crate::hydrate::spawn_hydrator;
+use crate::orderflow::PoolSnapshot;
+use crate::txpool::{PendingTx, PoolStatus, QueuedPool};
+use crate::pool::EndpointPool;
+use crate::ratelimit::{RateLimitConfig, RateLimited, TokenBucket};
+use crate::seen::SeenSet;
+use crate::transport::Reconnect;
+
+/// Provider stack used by the terminal: a rate-limited pool of endpoints
+pub type NodeProvider = Provider<RateLimited<EndpointPool>>;
+
+/// Settings applied to every provider connection
+#[derive(Debug, Clone)]
+pub struct ConnectionConfig {
+    pub rate_limit: RateLimitConfig,
+    pub batch: BatchConfig,
 }
 
-/// Connect to an Ethereum node using the provided endpoint URL with retry mechanism
-pub async fn connect_to_node(endpoint: &str) -> Result<Provider<Http>, Box<dyn std::error::Error>> {
+/// Delay before re-issuing `eth_subscribe` after a push subscription ends
+const RESUBSCRIBE_DELAY: Duration = Duration::from_secs(1);
+
+/// Connect to one or more Ethereum nodes using the provided endpoint URLs with retry mechanism
+///
+/// The transport of each endpoint is selected from its form: `http(s)://` polls, while `ws(s)://`
+/// URLs and IPC socket paths use push subscriptions. Requests are routed to the healthiest endpoint,
+/// every request is charged against `bucket`, shared by all providers so the rate limit holds across
+/// them, and HTTP calls are coalesced into batch requests according to `config.batch`.
+pub async fn connect_to_node(
+    endpoints: &[String],
+    config: &ConnectionConfig,
+    bucket: Arc<TokenBucket>,
+) -> Result<NodeProvider, NodeError> {
     // Create provider with error handling for invalid URLs
-    let provider = match Provider::<Http>::try_from(endpoint) {
-        Ok(p) => p.interval(Duration::from_millis(10)), // Set polling interval
+    let provider = match EndpointPool::connect(endpoints, &config.batch, Arc::clone(&bucket)).await {
+        Ok(pool) => Provider::new(RateLimited::new(pool, bucket)).interval(Duration::from_millis(10)), // Set polling interval
         Err(e) => {
-            error!("Failed to create provider: Invalid endpoint URL format");
-            return Err(Box::new(e));
+            error!("Failed to create provider: {}", e);
+            return Err(e);
         }
     };
     
@@ -47,35 +61,31 @@ pub async fn connect_to_node(endpoint: &str) -> Result<Provider<Http>, Box<dyn s
             Ok(block_result) => {
                 match block_result {
                     Ok(block_number) => {
-                        info!("Connected to Ethereum node. Current block: {}", block_number);
+                        info!("Connected to Ethereum node over {}. Current block: {}", provider.as_ref().inner().kind(), block_number);
                         return Ok(provider);
                     },
                     Err(e) => {
-                        // Check for specific error codes
-                        if let Some(error_code) = get_error_code(&e) {
-                            match error_code {
-                                -32046 => {
-                                    warn!("Connection attempt {}/{}: Cannot fulfill request (code: -32046). This endpoint may be rate-limited or unavailable.", 
-                                        retry_count + 1, max_retries);
-                                },
-                                -32601 => {
-                                    error!("Failed to connect to Ethereum node: Method not found (code: -32601). The endpoint may not support the required RPC methods.");
-                                    // Don't retry for method not found errors
-                                    return Err(Box::new(e));
-                                },
-                                _ => {
-                                    warn!("Connection attempt {}/{} failed: {}", retry_count + 1, max_retries, e);
-                                }
+                        let e = NodeError::from(e);
+                        match &e {
+                            NodeError::RateLimited { code, .. } => {
+                                warn!("Connection attempt {}/{}: Cannot fulfill request (code: {}). This endpoint may be rate-limited or unavailable.", 
+                                    retry_count + 1, max_retries, code);
+                            },
+                            NodeError::MethodNotFound(_) | NodeError::Unauthorized(_) => {
+                                error!("Failed to connect to Ethereum node: {}", e);
+                                // Retrying will not change the outcome
+                                return Err(e);
+                            },
+                            _ => {
+                                warn!("Connection attempt {}/{} failed: {}", retry_count + 1, max_retries, e);
                             }
-                        } else {
-                            warn!("Connection attempt {}/{} failed: {}", retry_count + 1, max_retries, e);
                         }
                         
                         // Check if we should retry
                         retry_count += 1;
                         if retry_count >= max_retries {
                             error!("Failed to connect to Ethereum node after {} attempts: {}", max_retries, e);
-                            return Err(Box::new(e));
+                            return Err(e);
                         }
                         
                         // Wait before retrying
@@ -92,7 +102,7 @@ pub async fn connect_to_node(endpoint: &str) -> Result<Provider<Http>, Box<dyn s
                 retry_count += 1;
                 if retry_count >= max_retries {
                     error!("Connection timeout after {} attempts: Ethereum node is unreachable or not responding", max_retries);
-                    return Err("Connection timeout: Ethereum node is unreachable or not responding".into());
+                    return Err(NodeError::Timeout("Ethereum node is unreachable or not responding".to_string()));
                 }
                 
                 // Wait before retrying
@@ -103,70 +113,107 @@ pub async fn connect_to_node(endpoint: &str) -> Result<Provider<Http>, Box<dyn s
     }
 }
 
+/// Hashes of every pending and queued transaction in the node's pool, `None` without `txpool_content`
+pub async fn pool_snapshot<M: Middleware>(provider: &M) -> Option<PoolSnapshot> {
+    let content = provider.txpool_content().await.ok()?;
+    // Read after the pool, so every block mined before the snapshot is at or below the head
+    let head = provider.get_block_number().await.ok()?;
+    Some(PoolSnapshot {
+        hashes: content
+            .pending
+            .values()
+            .chain(content.queued.values())
+            .flat_map(|txs| txs.values().map(|tx| tx.hash))
+            .collect(),
+        head,
+    })
+}
+
 /// Subscribe to pending transactions and send them to the provided channel
-pub async fn subscribe_to_pending_transactions(
-    provider: Arc<Provider<Http>>,
-    tx_sender: mpsc::Sender<Transaction>,
+///
+/// Polls `txpool_content`, so it works over any middleware stack, including request/response-only transports.
+/// Transactions already in `seen` are not sent again. Hashes from the fallback filter are resolved
+/// by a hydration stage running up to `fetch_concurrency` lookups in parallel.
+///
+/// Transactions entering the queued pool are sent to `queued_sender` with the reason they are stuck,
+/// and queued transactions that move to the pending pool are tagged as promoted.
+pub async fn subscribe_to_pending_transactions<M: Middleware + 'static>(
+    provider: Arc<M>,
+    tx_sender: mpsc::Sender<PendingTx>,
+    queued_sender: Option<mpsc::Sender<PendingTx>>,
     rate_limit: u32,
-) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    seen: Arc<Mutex<SeenSet>>,
+    fetch_concurrency: usize,
+) -> Result<(), NodeError> {
     info!("Subscribing to pending transactions with rate limit: {} queries/second", rate_limit);
     
-    // Calculate delay between requests to maintain rate limit
-    let delay_ms = 1000 / rate_limit;
-    let delay = Duration::from_millis(delay_ms as u64);
+    let hydrator = spawn_hydrator(Arc::clone(&provider), tx_sender.clone(), fetch_concurrency);
+    
+    // Poll interval, the provider's token bucket enforces the overall query budget
+    let delay = if rate_limit > 0 {
+        Duration::from_secs(1) / rate_limit
+    } else {
+        Duration::ZERO
+    };
     
     // Get pending transactions using a polling approach
-    let mut last_txs = Vec::new();
+    let mut txpool_supported = true;
+    let mut pending_filter = None;
+    let mut queued_pool = QueuedPool::default();
     
     loop {
         let start = Instant::now();
         
         // Get pending transactions from mempool
-        match provider.txpool_content().await {
-            Ok(content) => {
-                let mut new_txs = Vec::new();
-                
-                // Process pending transactions
-                for (_, txs) in content.pending.iter() {
-                    for (_, tx_obj) in txs.iter() {
-                        let tx_hash = tx_obj.hash;
-                        
-                        // Check if we've already processed this transaction
-                        if !last_txs.contains(&tx_hash) {
-                            // Get full transaction details
-                            if let Ok(Some(tx)) = provider.get_transaction(tx_hash).await {
-                                if let Err(e) = tx_sender.send(tx).await {
-                                    error!("Failed to send transaction to channel: {}", e);
-                                }
-                            }
-                            new_txs.push(tx_hash);
+        let txpool_result = if txpool_supported {
+            Some(provider.txpool_content().await)
+        } else {
+            None
+        };
+        
+        match txpool_result {
+            Some(Ok(content)) => {
+                let update = queued_pool.update(&content);
+                if let Some(queued_sender) = &queued_sender {
+                    for (tx, reason) in update.entered {
+                        if let Err(e) = queued_sender.send(PendingTx::with_status(tx, PoolStatus::Queued(reason))).await {
+                            error!("Failed to send queued transaction to channel: {}", e);
                         }
                     }
                 }
                 
-                // Update last seen transactions, keeping only the most recent ones
-                last_txs = new_txs;
-                if last_txs.len() > 10000 {
-                    last_txs.drain(0..5000); // Prevent unbounded growth
+                // Process pending transactions, txpool entries are already full transaction objects
+                let new_txs: Vec<Transaction> = {
+                    let mut seen = seen.lock().unwrap();
+                    content.pending.into_values()
+                        .flat_map(|txs| txs.into_values())
+                        .filter(|tx| seen.insert(tx.hash))
+                        .collect()
+                };
+                
+                for tx in new_txs {
+                    let status = match update.promoted.get(&tx.hash) {
+                        Some(&queued_for) => PoolStatus::Promoted { queued_for },
+                        None => PoolStatus::Pending,
+                    };
+                    if let Err(e) = tx_sender.send(PendingTx::with_status(tx, status)).await {
+                        error!("Failed to send transaction to channel: {}", e);
+                    }
                 }
             }
-            Err(e) => {
-                warn!("Failed to get pending transactions: {}", e);
-                // Fallback method: get pending transactions from block
-                if let Ok(Some(block)) = provider.get_block(BlockNumber::Pending).await {
-                    for tx_hash in &block.transactions {
-                        if let Ok(Some(tx)) = provider.get_transaction(*tx_hash).await {
-                            if !last_txs.contains(&tx.hash) {
-                                // Clone the transaction before sending it
-                                let tx_clone = tx.clone();
-                                if let Err(e) = tx_sender.send(tx_clone).await {
-                                    error!("Failed to send transaction to channel: {}", e);
-                                }
-                                last_txs.push(tx.hash);
-                            }
+            result => {
+                if let Some(Err(e)) = result {
+                    if let NodeError::MethodNotFound(_) = NodeError::from_middleware(&e) {
+                        if queued_sender.is_some() {
+                            warn!("Endpoint does not support txpool_content, queued transactions cannot be monitored");
                         }
+                        info!("Endpoint does not support txpool_content, using fallback sources");
+                        txpool_supported = false;
+                    } else {
+                        warn!("Failed to get pending transactions: {}", e);
                     }
                 }
+                poll_pending_fallback(provider.as_ref(), &tx_sender, &hydrator, &mut pending_filter, &seen).await;
             }
         }
         
@@ -178,11 +225,133 @@ pub async fn subscribe_to_pending_transactions(
     }
 }
 
+/// Fallback for endpoints without `txpool_content`
+///
+/// Prefers an `eth_newPendingTransactionFilter`, which only returns hashes that are handed to the
+/// hydration stage, and falls back to the full transaction objects of the pending block.
+async fn poll_pending_fallback<M: Middleware + 'static>(
+    provider: &M,
+    tx_sender: &mpsc::Sender<PendingTx>,
+    hydrator: &mpsc::Sender<(H256, Instant)>,
+    pending_filter: &mut Option<U256>,
+    seen: &Mutex<SeenSet>,
+) {
+    if pending_filter.is_none() {
+        *pending_filter = provider.new_filter(FilterKind::PendingTransactions).await.ok();
+    }
+    
+    if let Some(filter_id) = *pending_filter {
+        match provider.get_filter_changes::<_, H256>(filter_id).await {
+            Ok(hashes) => {
+                let seen_at = Instant::now();
+                for tx_hash in hashes {
+                    if !seen.lock().unwrap().insert(tx_hash) {
+                        continue;
+                    }
+                    if let Err(e) = hydrator.send((tx_hash, seen_at)).await {
+                        error!("Failed to queue transaction hash for hydration: {}", e);
+                    }
+                }
+                return;
+            }
+            Err(e) => {
+                // Filters expire when not polled in time, reinstall on the next round
+                warn!("Pending transaction filter failed: {}", e);
+                *pending_filter = None;
+            }
+        }
+    }
+    
+    // Fallback method: get pending transactions from block
+    if let Ok(Some(block)) = provider.get_block_with_txs(BlockNumber::Pending).await {
+        let new_txs: Vec<Transaction> = {
+            let mut seen = seen.lock().unwrap();
+            block.transactions.into_iter().filter(|tx| seen.insert(tx.hash)).collect()
+        };
+        for tx in new_txs {
+            if let Err(e) = tx_sender.send(PendingTx::new(tx)).await {
+                error!("Failed to send transaction to channel: {}", e);
+            }
+        }
+    }
+}
+
+/// Receive pending transaction hashes pushed via `eth_subscribe("newPendingTransactions")`
+///
+/// The subscription is re-issued whenever the stream ends, e.g. after the socket dropped. If that
+/// fails because the client ran out of reconnect attempts, the socket connection is opened anew.
+/// Hashes already in `seen` are skipped, new ones are resolved by a hydration stage running up to
+/// `fetch_concurrency` lookups in parallel.
+pub async fn stream_pending_transactions<M>(
+    provider: Arc<M>,
+    tx_sender: mpsc::Sender<PendingTx>,
+    seen: Arc<Mutex<SeenSet>>,
+    fetch_concurrency: usize,
+) -> Result<(), NodeError>
+where
+    M: Middleware + 'static,
+    M::Provider: PubsubClient + Reconnect,
+{
+    info!("Subscribing to pending transactions via eth_subscribe");
+    
+    let hydrator = spawn_hydrator(Arc::clone(&provider), tx_sender, fetch_concurrency);
+    
+    loop {
+        match provider.subscribe_pending_txs().await {
+            Ok(mut stream) => {
+                info!("Pending transaction subscription active (id: {})", stream.id);
+                while let Some(tx_hash) = stream.next().await {
+                    // Resubscribing can replay hashes that were already delivered
+                    if !seen.lock().unwrap().insert(tx_hash) {
+                        continue;
+                    }
+                    // Notifications only carry the hash, the full transaction is fetched by the hydrator
+                    if let Err(e) = hydrator.send((tx_hash, Instant::now())).await {
+                        error!("Failed to queue transaction hash for hydration: {}", e);
+                    }
+                }
+                warn!("Pending transaction subscription ended, resubscribing...");
+            }
+            Err(e) => {
+                warn!("Failed to subscribe to pending transactions: {}", e);
+                // Failures are logged per endpoint, the subscription is retried either way
+                let _ = provider.provider().as_ref().reconnect().await;
+            }
+        }
+        
+        sleep(RESUBSCRIBE_DELAY).await;
+    }
+}
+
+/// Bring the tracked chain up to `head` and forward every new block and reorg in order
+async fn send_block_events<M: Middleware>(
+    provider: &M,
+    chain: &mut ChainTracker,
+    head: NewBlock,
+    block_sender: &mpsc::Sender<BlockEvent>,
+) -> Result<(), NodeError> {
+    for event in follow_head(provider, chain, head).await? {
+        if let BlockEvent::Reorg(reorg) = &event {
+            warn!("Chain reorg of depth {} above block {}", reorg.depth(), reorg.common_ancestor);
+        }
+        if let Err(e) = block_sender.send(event).await {
+            error!("Failed to send block to channel: {}", e);
+        }
+    }
+    Ok(())
+}
+
 /// Subscribe to new blocks and send them to the provided channel
-pub async fn subscribe_to_blocks(
-    provider: Arc<Provider<Http>>,
-    block_sender: mpsc::Sender<Block<TxHash>>,
-) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+///
+/// Polls the block number once per second, so it works over any middleware stack. Blocks skipped
+/// between two polls are backfilled and reorgs are detected from the parent hashes, a reorg that
+/// keeps the chain height is noticed with the next block. `chain` holds the blocks already sent,
+/// e.g. by a historical scan, the feed continues from its tip.
+pub async fn subscribe_to_blocks<M: Middleware + 'static>(
+    provider: Arc<M>,
+    block_sender: mpsc::Sender<BlockEvent>,
+    mut chain: ChainTracker,
+) -> Result<(), NodeError> {
     info!("Subscribing to new blocks");
     
     let mut last_block_number: Option<U64> = None;
@@ -190,21 +359,20 @@ pub async fn subscribe_to_blocks(
     loop {
         // Get latest block number
         match provider.get_block_number().await {
-            Ok(current_block) => {
-                // Check if we have a new block
-                if let Some(last_number) = last_block_number {
-                    if current_block > last_number {
-                        // Get block details
-                        if let Ok(Some(block)) = provider.get_block(current_block).await {
-                            if let Err(e) = block_sender.send(block).await {
-                                error!("Failed to send block to channel: {}", e);
-                            }
+            Ok(current_block) if last_block_number != Some(current_block) => {
+                // Get block details, the number is only remembered once the block was processed
+                match fetch_block(provider.as_ref(), current_block, chain.detail()).await {
+                    Ok(Some(block)) => {
+                        match send_block_events(provider.as_ref(), &mut chain, block, &block_sender).await {
+                            Ok(()) => last_block_number = Some(current_block),
+                            Err(e) => error!("Failed to follow block {}: {}", current_block, e),
                         }
                     }
+                    Ok(None) => {}
+                    Err(e) => error!("Failed to get block {}: {}", current_block, e),
                 }
-                
-                last_block_number = Some(current_block);
             }
+            Ok(_) => {}
             Err(e) => {
                 error!("Failed to get latest block number: {}", e);
             }
@@ -215,26 +383,79 @@ pub async fn subscribe_to_blocks(
     }
 }
 
+/// Receive new block headers pushed via `eth_subscribe("newHeads")`
+///
+/// Headers do not carry transaction hashes, so the full block is fetched by hash before sending.
+/// A block that cannot be fetched is skipped and backfilled from the parent hash of the next head.
+/// Missed blocks are backfilled and reorgs detected like in [`subscribe_to_blocks`], the
+/// subscription is re-issued like in [`stream_pending_transactions`].
+pub async fn stream_blocks<M>(
+    provider: Arc<M>,
+    block_sender: mpsc::Sender<BlockEvent>,
+    mut chain: ChainTracker,
+) -> Result<(), NodeError>
+where
+    M: Middleware + 'static,
+    M::Provider: PubsubClient + Reconnect,
+{
+    info!("Subscribing to new blocks via eth_subscribe");
+    
+    loop {
+        match provider.subscribe_blocks().await {
+            Ok(mut stream) => {
+                info!("Block subscription active (id: {})", stream.id);
+                while let Some(header) = stream.next().await {
+                    let Some(hash) = header.hash else {
+                        continue;
+                    };
+                    let block = match fetch_with_retry(provider.as_ref(), hash, chain.detail()).await {
+                        Ok(block) => block,
+                        Err(e) => {
+                            warn!("Skipping block {:?} until the next head: {}", hash, e);
+                            continue;
+                        }
+                    };
+                    if let Err(e) = send_block_events(provider.as_ref(), &mut chain, block, &block_sender).await {
+                        error!("Failed to follow block {:?}: {}", hash, e);
+                    }
+                }
+                warn!("Block subscription ended, resubscribing...");
+            }
+            Err(e) => {
+                warn!("Failed to subscribe to new blocks: {}", e);
+                // Failures are logged per endpoint, the subscription is retried either way
+                let _ = provider.provider().as_ref().reconnect().await;
+            }
+        }
+        
+        sleep(RESUBSCRIBE_DELAY).await;
+    }
+}
+
 /// Get detailed transaction information including receipt
 #[allow(dead_code)]
-pub async fn get_transaction_details(
-    provider: &Provider<Http>,
+pub async fn get_transaction_details<M: Middleware + 'static>(
+    provider: &M,
     tx_hash: H256,
-) -> Result<(Transaction, Option<TransactionReceipt>), Box<dyn std::error::Error + Send + Sync>> {
-    let tx = provider.get_transaction(tx_hash).await?
-        .ok_or("Transaction not found")?;
-    
-    let receipt = provider.get_transaction_receipt(tx_hash).await?;
+) -> Result<(Transaction, Option<TransactionReceipt>), NodeError> {
+    // Issued together so a batching transport sends both in one request
+    let (tx, receipt) = futures::join!(
+        provider.get_transaction(tx_hash),
+        provider.get_transaction_receipt(tx_hash),
+    );
+    let tx = tx.map_err(|e| NodeError::from_middleware(&e))?
+        .ok_or_else(|| NodeError::Other("Transaction not found".to_string()))?;
+    let receipt = receipt.map_err(|e| NodeError::from_middleware(&e))?;
     
     Ok((tx, receipt))
 }
 
 /// Get contract ABI for a verified contract
 #[allow(dead_code)]
-pub async fn get_contract_abi(
-    _provider: &Provider<Http>,
+pub async fn get_contract_abi<M: Middleware>(
+    _provider: &M,
     _contract_address: Address,
-) -> Result<Option<ethers::abi::Abi>, Box<dyn std::error::Error + Send + Sync>> {
+) -> Result<Option<ethers::abi::Abi>, NodeError> {
     // This is a simplified implementation
     // In a real-world scenario, you would query Etherscan or similar services
     // to get the ABI for verified contracts
@@ -248,7 +469,7 @@ pub async fn get_contract_abi(
 pub fn decode_transaction_input(
     tx: &Transaction,
     abi: &ethers::abi::Abi,
-) -> Result<String, Box<dyn std::error::Error + Send + Sync>> {
+) -> Result<String, NodeError> {
     // Input is not an Option type in Transaction
     let input = &tx.input;
     if input.0.len() >= 4 {
@@ -261,7 +482,8 @@ pub fn decode_transaction_input(
             if selector == function_selector {
                 // Found matching function, now decode parameters
                 let params = &input.0[4..];
-                let decoded = function.decode_input(params)?;
+                let decoded = function.decode_input(params)
+                    .map_err(|e| NodeError::Decode(e.to_string()))?;
                 
                 // Format decoded parameters
                 let mut result = format!("{}(", function.name);
@@ -271,7 +493,7 @@ pub fn decode_transaction_input(
                     }
                     result.push_str(&format!("{}", param));
                 }
-                result.push_str(")");
+                result.push(')');
                 
                 return Ok(result);
             }
@@ -280,4 +502,177 @@ pub fn decode_transaction_input(
     
     // Fallback: return hex data
     Ok(format!("Data: {}", &tx.input))
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ratelimit::RateLimitConfig;
+    use crate::transport::Transport;
+    use crate::txpool::QueuedReason;
+    use ethers::providers::{JsonRpcClient, MockProvider};
+    use std::collections::BTreeMap;
+    use serde_json::{json, Value};
+    use std::path::{Path, PathBuf};
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::{UnixListener, UnixStream};
+    use tokio::task::JoinHandle;
+    use tokio::time::timeout;
+
+    const TEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+    fn connection_config() -> ConnectionConfig {
+        ConnectionConfig {
+            rate_limit: RateLimitConfig { rate: 0, burst: 0, weights: Default::default(), adaptive: false },
+            batch: BatchConfig { max_size: 1, window: Duration::ZERO },
+        }
+    }
+
+    fn socket_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("ultreth-{}-{}.ipc", name, std::process::id()))
+    }
+
+    /// Stand-in for a node's IPC socket, after `eth_subscribe` `pending_hash` is pushed until the client hangs up
+    fn serve_ipc(path: &Path, pending_hash: H256) -> JoinHandle<()> {
+        let _ = std::fs::remove_file(path);
+        let listener = UnixListener::bind(path).unwrap();
+        tokio::spawn(async move {
+            while let Ok((socket, _)) = listener.accept().await {
+                tokio::spawn(serve_connection(socket, pending_hash));
+            }
+        })
+    }
+
+    async fn serve_connection(mut socket: UnixStream, pending_hash: H256) {
+        let mut buffer = Vec::new();
+        let mut subscription = None;
+        loop {
+            let mut chunk = [0u8; 4096];
+            let read = tokio::select! {
+                read = socket.read(&mut chunk) => read,
+                // The client only listens for notifications once it registered the subscription id
+                _ = sleep(Duration::from_millis(50)), if subscription.is_some() => {
+                    let notification = json!({
+                        "jsonrpc": "2.0",
+                        "method": "eth_subscription",
+                        "params": { "subscription": subscription, "result": pending_hash },
+                    });
+                    if socket.write_all(notification.to_string().as_bytes()).await.is_err() {
+                        return;
+                    }
+                    continue;
+                }
+            };
+            match read {
+                Ok(0) | Err(_) => return,
+                Ok(read) => buffer.extend_from_slice(&chunk[..read]),
+            }
+
+            let mut requests = serde_json::Deserializer::from_slice(&buffer).into_iter::<Value>();
+            let mut consumed = 0;
+            let mut responses = Vec::new();
+            while let Some(Ok(request)) = requests.next() {
+                consumed = requests.byte_offset();
+                let result = match request["method"].as_str().unwrap_or_default() {
+                    "eth_blockNumber" => json!("0x10"),
+                    "eth_subscribe" => {
+                        subscription = Some("0x1");
+                        json!("0x1")
+                    }
+                    "eth_getTransactionByHash" => {
+                        let hash: H256 = serde_json::from_value(request["params"][0].clone()).unwrap();
+                        serde_json::to_value(Transaction { hash, ..Default::default() }).unwrap()
+                    }
+                    _ => Value::Null,
+                };
+                responses.push(json!({ "jsonrpc": "2.0", "id": request["id"], "result": result }));
+            }
+            buffer.drain(..consumed);
+            for response in responses {
+                if socket.write_all(response.to_string().as_bytes()).await.is_err() {
+                    return;
+                }
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn ipc_transport_speaks_json_rpc() {
+        let path = socket_path("transport");
+        let server = serve_ipc(&path, H256::zero());
+
+        let transport = Transport::connect(path.to_str().unwrap(), &connection_config().batch).await.unwrap();
+        assert_eq!(transport.kind(), "IPC");
+        assert!(transport.supports_pubsub());
+        let block_number: U64 = JsonRpcClient::request(&transport, "eth_blockNumber", ()).await.unwrap();
+        assert_eq!(block_number, U64::from(16));
+
+        server.abort();
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[tokio::test]
+    async fn streams_pending_transactions_over_ipc() {
+        let path = socket_path("pending");
+        let pending_hash = H256::repeat_byte(0xab);
+        let server = serve_ipc(&path, pending_hash);
+
+        let config = connection_config();
+        let bucket = Arc::new(TokenBucket::new(&config.rate_limit));
+        let provider = connect_to_node(&[path.to_string_lossy().to_string()], &config, bucket).await.unwrap();
+        let (tx_sender, mut tx_receiver) = mpsc::channel(10);
+        let seen = Arc::new(Mutex::new(SeenSet::new(100, Duration::from_secs(60))));
+        let feed = tokio::spawn(stream_pending_transactions(Arc::new(provider), tx_sender, Arc::clone(&seen), 4));
+
+        let pending = timeout(TEST_TIMEOUT, tx_receiver.recv()).await.unwrap().unwrap();
+        assert_eq!(pending.tx.hash, pending_hash);
+        assert_eq!(pending.status, PoolStatus::Pending);
+        // Repeated notifications of the same hash are suppressed
+        assert!(timeout(Duration::from_millis(300), tx_receiver.recv()).await.is_err());
+        assert_eq!(seen.lock().unwrap().inserted(), 1);
+
+        feed.abort();
+        server.abort();
+        let _ = std::fs::remove_file(&path);
+    }
+
+    fn pool_tx(hash: u8, from: Address, nonce: u64) -> Transaction {
+        Transaction { hash: H256::repeat_byte(hash), from, nonce: nonce.into(), ..Default::default() }
+    }
+
+    #[tokio::test]
+    async fn polls_txpool_content_into_the_channels() {
+        let sender = Address::repeat_byte(0x11);
+        let pending = pool_tx(0x01, sender, 0);
+        let queued = pool_tx(0x02, sender, 3);
+        let content = TxpoolContent {
+            pending: BTreeMap::from([(sender, BTreeMap::from([("0".to_string(), pending.clone())]))]),
+            queued: BTreeMap::from([(sender, BTreeMap::from([("3".to_string(), queued.clone())]))]),
+        };
+        let mock = MockProvider::new();
+        // The same snapshot twice, the second poll must not deliver anything again
+        mock.push(content.clone()).unwrap();
+        mock.push(content).unwrap();
+
+        let (tx_sender, mut tx_receiver) = mpsc::channel(10);
+        let (queued_sender, mut queued_receiver) = mpsc::channel(10);
+        let seen = Arc::new(Mutex::new(SeenSet::new(100, Duration::from_secs(60))));
+        let feed = tokio::spawn(subscribe_to_pending_transactions(Arc::new(Provider::new(mock.clone())), tx_sender,
+            Some(queued_sender), 100, seen, 4));
+
+        let received = timeout(TEST_TIMEOUT, tx_receiver.recv()).await.unwrap().unwrap();
+        assert_eq!(received.tx.hash, pending.hash);
+        assert_eq!(received.status, PoolStatus::Pending);
+        let received = timeout(TEST_TIMEOUT, queued_receiver.recv()).await.unwrap().unwrap();
+        assert_eq!(received.tx.hash, queued.hash);
+        assert_eq!(received.status, PoolStatus::Queued(QueuedReason::MissingNonce(U256::one())));
+
+        sleep(Duration::from_millis(100)).await;
+        assert!(tx_receiver.try_recv().is_err());
+        assert!(queued_receiver.try_recv().is_err());
+        mock.assert_request("txpool_content", ()).unwrap();
+        mock.assert_request("txpool_content", ()).unwrap();
+
+        feed.abort();
+    }
+}
@@ -0,0 +1,90 @@
+use ethers::prelude::*;
+use futures::stream::{self, StreamExt};
+use log::{debug, error};
+use std::sync::Arc;
+use std::time::Instant;
+use tokio::sync::mpsc;
+
+use crate::error::NodeError;
+use crate::txpool::{PendingTx, PoolStatus};
+
+/// Number of hashes that can wait for hydration before the feed is slowed down
+const HASH_QUEUE_SIZE: usize = 10_000;
+
+/// Start a hydration stage resolving pending transaction hashes to full transactions
+///
+/// Up to `concurrency` `eth_getTransactionByHash` lookups run in parallel, all of them charged
+/// against the provider's rate budget. Transactions that were mined (or dropped from the pool)
+/// before their lookup completed are not forwarded. Returns the sender to feed hashes into,
+/// each tagged with its arrival time.
+pub fn spawn_hydrator<M: Middleware + 'static>(
+    provider: Arc<M>,
+    tx_sender: mpsc::Sender<PendingTx>,
+    concurrency: usize,
+) -> mpsc::Sender<(H256, Instant)> {
+    let (hash_sender, mut hash_receiver) = mpsc::channel::<(H256, Instant)>(HASH_QUEUE_SIZE);
+
+    tokio::spawn(async move {
+        stream::poll_fn(|cx| hash_receiver.poll_recv(cx))
+            .map(|(hash, seen_at)| {
+                let provider = Arc::clone(&provider);
+                async move { (hash, seen_at, provider.get_transaction(hash).await) }
+            })
+            .buffer_unordered(concurrency.max(1))
+            .for_each(|(hash, seen_at, result)| {
+                let tx_sender = tx_sender.clone();
+                async move {
+                    match result {
+                        Ok(Some(tx)) if tx.block_number.is_some() => {
+                            debug!("Transaction {:?} was mined before hydration completed, dropping", hash);
+                        }
+                        Ok(Some(tx)) => {
+                            if let Err(e) = tx_sender.send(PendingTx { tx, seen_at, status: PoolStatus::Pending }).await {
+                                error!("Failed to send transaction to channel: {}", e);
+                            }
+                        }
+                        Ok(None) => {
+                            debug!("Transaction {:?} left the mempool before hydration completed, dropping", hash);
+                        }
+                        Err(e) => {
+                            debug!("Failed to hydrate transaction {:?}: {}", hash, NodeError::from_middleware(&e));
+                        }
+                    }
+                }
+            })
+            .await;
+    });
+
+    hash_sender
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ethers::providers::MockProvider;
+    use std::time::Duration;
+    use tokio::time::timeout;
+
+    #[tokio::test]
+    async fn forwards_only_transactions_still_pending() {
+        let pending = Transaction { hash: H256::repeat_byte(1), ..Default::default() };
+        let mined = Transaction { hash: H256::repeat_byte(3), block_number: Some(100.into()), ..Default::default() };
+        let mock = MockProvider::new();
+        // One lookup at a time, responses are served last in, first out
+        mock.push(mined).unwrap();
+        mock.push::<Option<Transaction>, _>(None).unwrap();
+        mock.push(pending.clone()).unwrap();
+        let (tx_sender, mut tx_receiver) = mpsc::channel(10);
+        let hash_sender = spawn_hydrator(Arc::new(Provider::new(mock)), tx_sender, 1);
+
+        let seen_at = Instant::now();
+        for hash in [1, 2, 3].map(H256::repeat_byte) {
+            hash_sender.send((hash, seen_at)).await.unwrap();
+        }
+
+        let hydrated = timeout(Duration::from_secs(5), tx_receiver.recv()).await.unwrap().unwrap();
+        assert_eq!(hydrated.tx.hash, pending.hash);
+        assert_eq!(hydrated.seen_at, seen_at);
+        assert!(timeout(Duration::from_millis(200), tx_receiver.recv()).await.is_err());
+    }
+}
@@ -0,0 +1,103 @@
+use std::collections::VecDeque;
+use std::time::Duration;
+
+/// Number of most recent inclusion latencies kept for the distribution summary
+const MAX_SAMPLES: usize = 100_000;
+
+/// Percentiles of the time from first sighting to inclusion
+#[derive(Debug, Clone)]
+pub struct LatencySummary {
+    pub samples: usize,
+    pub min: Duration,
+    pub p50: Duration,
+    pub p90: Duration,
+    pub p99: Duration,
+    pub max: Duration,
+    pub mean: Duration,
+}
+
+/// Final outcome of every transaction that left the pending or queued pool
+#[derive(Debug, Default)]
+pub struct InclusionStats {
+    pub mined: u64,
+    pub replaced: u64,
+    pub dropped: u64,
+    /// Time-to-mine of the most recently mined transactions
+    latencies: VecDeque<Duration>,
+}
+
+impl InclusionStats {
+    /// Record a transaction mined `latency` after it was first seen
+    pub fn record_mined(&mut self, latency: Duration) {
+        self.mined += 1;
+        self.latencies.push_back(latency);
+        if self.latencies.len() > MAX_SAMPLES {
+            self.latencies.pop_front();
+        }
+    }
+
+    /// Take back a mined transaction whose block was orphaned
+    pub fn record_unmined(&mut self, latency: Duration) {
+        self.mined = self.mined.saturating_sub(1);
+        if let Some(index) = self.latencies.iter().rposition(|sample| *sample == latency) {
+            self.latencies.remove(index);
+        }
+    }
+
+    /// Total number of transactions with a final outcome
+    pub fn total(&self) -> u64 {
+        self.mined + self.replaced + self.dropped
+    }
+
+    /// Distribution of the recorded time-to-mine, `None` until a transaction was mined
+    pub fn latency_summary(&self) -> Option<LatencySummary> {
+        let mut sorted: Vec<Duration> = self.latencies.iter().copied().collect();
+        sorted.sort_unstable();
+        let percentile = |p: usize| sorted[(sorted.len() - 1) * p / 100];
+
+        Some(LatencySummary {
+            samples: sorted.len(),
+            min: *sorted.first()?,
+            p50: percentile(50),
+            p90: percentile(90),
+            p99: percentile(99),
+            max: *sorted.last()?,
+            mean: sorted.iter().sum::<Duration>() / sorted.len() as u32,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn summarizes_the_latency_distribution() {
+        let mut stats = InclusionStats::default();
+        assert!(stats.latency_summary().is_none());
+        for seconds in 1..=100 {
+            stats.record_mined(Duration::from_secs(seconds));
+        }
+        stats.replaced = 2;
+        stats.dropped = 3;
+
+        let summary = stats.latency_summary().unwrap();
+        assert_eq!(summary.samples, 100);
+        assert_eq!((summary.min, summary.max), (Duration::from_secs(1), Duration::from_secs(100)));
+        assert_eq!((summary.p50, summary.p90, summary.p99), (Duration::from_secs(50), Duration::from_secs(90), Duration::from_secs(99)));
+        assert_eq!(summary.mean, Duration::from_millis(50_500));
+        assert_eq!(stats.total(), 105);
+    }
+
+    #[test]
+    fn takes_back_orphaned_inclusions() {
+        let mut stats = InclusionStats::default();
+        stats.record_mined(Duration::from_secs(12));
+        stats.record_mined(Duration::from_secs(30));
+        stats.record_unmined(Duration::from_secs(12));
+
+        assert_eq!(stats.mined, 1);
+        let summary = stats.latency_summary().unwrap();
+        assert_eq!((summary.samples, summary.min), (1, Duration::from_secs(30)));
+    }
+}
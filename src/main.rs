@@ -1,33 +1,143 @@
 use clap::{Parser, Subcommand};
 use colored::*;
-use std::sync::Arc;
+use ethers::providers::Middleware;
+use ethers::types::{Address, Transaction};
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
 use std::time::{Duration, Instant};
 use tokio::sync::mpsc;
-use tokio::time::sleep;
+use tokio::time::interval;
 
+mod accesslist;
+mod analytics;
+mod authorization;
+mod backfill;
+mod batch;
+mod blob;
+mod chain;
 mod display;
+mod error;
 mod ethereum;
+mod hydrate;
+mod inclusion;
+mod mempool;
+mod orderflow;
+mod pool;
+mod race;
+mod ratelimit;
+mod seen;
+mod token;
+mod transport;
+mod txpool;
+mod units;
 mod utils;
 
-use display::format_transaction;
-use ethereum::{connect_to_node, subscribe_to_pending_transactions, subscribe_to_blocks};
-use utils::{setup_logger, calculate_query_rate};
+use accesslist::AccessListStats;
+use analytics::BlockEconomics;
+use backfill::{backfill_blocks, write_checkpoint, ScanRange};
+use batch::BatchConfig;
+use chain::{BlockDetail, BlockEvent, ChainTracker, NewBlock};
+use blob::{blob_count, BlobFees, BLOB_TX_TYPE};
+use display::{format_block_analytics, format_eth, format_gwei, format_transaction, DisplayOptions, FeeContext};
+use error::NodeError;
+use ethereum::{
+    connect_to_node, pool_snapshot, stream_blocks, stream_pending_transactions, subscribe_to_blocks,
+    subscribe_to_pending_transactions, ConnectionConfig, NodeProvider,
+};
+use mempool::{Mempool, ReplacementKind};
+use orderflow::{OrderflowTracker, WARMUP};
+use race::{merge_pending_streams, RaceTracker};
+use ratelimit::{RateLimitConfig, TokenBucket};
+use seen::SeenSet;
+use token::TokenCache;
+use txpool::{PendingTx, PoolStatus};
+use utils::{setup_logger, calculate_query_rate, parse_method_weight, read_endpoints_file};
+
+/// Endpoint used when none is given on the command line
+const DEFAULT_ENDPOINT: &str = "https://rpc.ankr.com/eth";
 
 #[derive(Parser)]
 #[command(author, version, about, long_about = None)]
 struct Cli {
-    /// Ethereum node endpoint URL
-    #[arg(short, long, default_value = "https://rpc.ankr.com/eth")]
-    endpoint: String,
+    /// Ethereum node endpoint URL or IPC socket path (http(s):// polls, ws(s):// and IPC use push subscriptions).
+    /// Repeat to pool several endpoints [default: https://rpc.ankr.com/eth]
+    #[arg(short, long)]
+    endpoint: Vec<String>,
+
+    /// File with one endpoint per line, added to the pool (blank lines and # comments are ignored)
+    #[arg(long)]
+    endpoints_file: Option<PathBuf>,
 
-    /// Maximum queries per second
+    /// Maximum queries per second across all RPC calls (0 disables rate limiting)
     #[arg(short, long, default_value_t = 30)]
     rate_limit: u32,
 
+    /// Lower the query rate when endpoints throttle (-32046, -32005, 429, timeouts) and recover on success
+    #[arg(long)]
+    adaptive: bool,
+
+    /// Maximum burst of queries after an idle period [default: the rate limit]
+    #[arg(long)]
+    burst: Option<u32>,
+
+    /// Budget charged per call of a method as METHOD=WEIGHT, e.g. provider compute units (repeatable)
+    #[arg(long = "method-weight", value_parser = parse_method_weight)]
+    method_weights: Vec<(String, u32)>,
+
+    /// Maximum number of calls coalesced into one JSON-RPC batch request over HTTP (1 disables batching)
+    #[arg(long, default_value_t = 1)]
+    batch_size: usize,
+
+    /// Milliseconds the first call of a batch waits for others to join
+    #[arg(long, default_value_t = 5)]
+    batch_window_ms: u64,
+
+    /// Maximum number of transaction hashes remembered for duplicate suppression
+    #[arg(long, default_value_t = 100_000)]
+    seen_capacity: usize,
+
+    /// Seconds after which an unseen transaction hash is forgotten by duplicate suppression
+    #[arg(long, default_value_t = 600)]
+    seen_ttl: u64,
+
+    /// Maximum number of parallel lookups resolving pending transaction hashes to full transactions
+    #[arg(long, default_value_t = 16)]
+    fetch_concurrency: usize,
+
+    /// Seconds after which a pending or queued transaction that was neither mined nor replaced counts as dropped
+    #[arg(long, default_value_t = 1800)]
+    drop_after: u64,
+
+    /// Fetch blocks with full transactions and receipts to show per-block fee analytics
+    #[arg(long)]
+    full_blocks: bool,
+
+    /// Only show blob-carrying (EIP-4844, type 3) transactions
+    #[arg(long)]
+    blobs_only: bool,
+
+    /// Only show transactions of this EIP-2718 type, e.g. 4 for EIP-7702 set-code (repeatable)
+    #[arg(long = "tx-type")]
+    tx_types: Vec<u64>,
+
+    /// Delegation target considered trusted, authorizations to any other address are flagged (repeatable)
+    #[arg(long = "known-delegate")]
+    known_delegates: Vec<Address>,
+
+    /// Round ETH, Gwei and token amounts half up to this many fraction digits [default: exact]
+    #[arg(long)]
+    decimals: Option<u32>,
+
     /// Log level (debug, info, warn, error)
     #[arg(short, long, default_value = "info")]
     log_level: String,
 
+    /// Connect to every endpoint separately, merge their pending streams and report which one saw each tx first
+    /// (pending and all commands only)
+    #[arg(long)]
+    race: bool,
+
     /// List available public Ethereum endpoints
     #[arg(short = 'L', long)]
     list_endpoints: bool,
@@ -40,10 +150,115 @@ struct Cli {
 enum Commands {
     /// Monitor pending transactions
     Pending,
-    /// Monitor new blocks
-    Blocks,
+    /// Monitor new blocks, or scan a range of historical blocks
+    Blocks {
+        /// First block to scan, blocks up to the head are scanned before following it
+        #[arg(long)]
+        from: Option<u64>,
+        /// Last block to scan (cut at the head), the program exits once the range is done
+        #[arg(long, requires = "from")]
+        to: Option<u64>,
+        /// File recording the last shown block, an interrupted scan resumes after it
+        #[arg(long, requires = "from")]
+        checkpoint: Option<PathBuf>,
+    },
     /// Monitor both pending transactions and new blocks
     All,
+    /// Monitor queued (nonce-gapped) transactions and their promotion to pending
+    Queued,
+}
+
+/// Spawn the pending transaction feed, using push subscriptions when the transport supports them
+///
+/// The queued pool is only visible in `txpool_content`, so the feed polls when `queued_sender` is set.
+fn spawn_pending_feed(
+    provider: Arc<NodeProvider>,
+    tx_sender: mpsc::Sender<PendingTx>,
+    queued_sender: Option<mpsc::Sender<PendingTx>>,
+    rate_limit: u32,
+    seen: Arc<Mutex<SeenSet>>,
+    fetch_concurrency: usize,
+) {
+    tokio::spawn(async move {
+        let result = if queued_sender.is_none() && (*provider).as_ref().inner().supports_pubsub() {
+            stream_pending_transactions(provider, tx_sender, seen, fetch_concurrency).await
+        } else {
+            subscribe_to_pending_transactions(provider, tx_sender, queued_sender, rate_limit, seen, fetch_concurrency).await
+        };
+        if let Err(e) = result {
+            eprintln!("Error in pending transactions subscription: {}", e);
+        }
+    });
+}
+
+/// Follow the chain head from the tip of `chain`, using push subscriptions when the transport supports them
+async fn follow_blocks(
+    provider: Arc<NodeProvider>,
+    block_sender: mpsc::Sender<BlockEvent>,
+    chain: ChainTracker,
+) -> Result<(), NodeError> {
+    if (*provider).as_ref().inner().supports_pubsub() {
+        stream_blocks(provider, block_sender, chain).await
+    } else {
+        subscribe_to_blocks(provider, block_sender, chain).await
+    }
+}
+
+/// Spawn the block feed following the chain head
+fn spawn_block_feed(provider: Arc<NodeProvider>, block_sender: mpsc::Sender<BlockEvent>, detail: BlockDetail) {
+    tokio::spawn(async move {
+        if let Err(e) = follow_blocks(provider, block_sender, ChainTracker::new(detail)).await {
+            eprintln!("Error in blocks subscription: {}", e);
+        }
+    });
+}
+
+/// Spawn a scan of historical blocks, followed by the live block feed if the range is open-ended
+fn spawn_backfill_feed(
+    provider: Arc<NodeProvider>,
+    block_sender: mpsc::Sender<BlockEvent>,
+    range: ScanRange,
+    fetch_concurrency: usize,
+    detail: BlockDetail,
+) {
+    tokio::spawn(async move {
+        let result = match backfill_blocks(Arc::clone(&provider), &range, fetch_concurrency, detail, &block_sender).await {
+            Ok(chain) if range.to.is_none() => follow_blocks(provider, block_sender, chain).await,
+            Ok(_) => Ok(()),
+            Err(e) => Err(e),
+        };
+        if let Err(e) = result {
+            eprintln!("Error in block scan: {}", e);
+        }
+    });
+}
+
+/// Connect to each endpoint separately and merge their pending feeds, tracking first-seen sources
+///
+/// Returns the duplicate suppression set of each endpoint's feed.
+async fn spawn_race_feed(
+    endpoints: &[String],
+    tx_sender: mpsc::Sender<PendingTx>,
+    config: &ConnectionConfig,
+    bucket: &Arc<TokenBucket>,
+    tracker: Arc<Mutex<RaceTracker>>,
+    new_seen: impl Fn() -> SeenSet,
+    fetch_concurrency: usize,
+) -> Result<Vec<Arc<Mutex<SeenSet>>>, NodeError> {
+    let mut receivers = Vec::new();
+    let mut seen_sets = Vec::new();
+    for endpoint in endpoints {
+        let provider = Arc::new(connect_to_node(std::slice::from_ref(endpoint), config, Arc::clone(bucket)).await?);
+        let (sender, receiver) = mpsc::channel(1000);
+        // Each endpoint deduplicates its own feed, cross-endpoint duplicates are handled by the tracker
+        let seen = Arc::new(Mutex::new(new_seen()));
+        spawn_pending_feed(provider, sender, None, config.rate_limit.rate, Arc::clone(&seen), fetch_concurrency);
+        receivers.push(receiver);
+        seen_sets.push(seen);
+    }
+    
+    tokio::spawn(merge_pending_streams(receivers, tx_sender, tracker));
+    Ok(seen_sets)
 }
 
 #[tokio::main]
@@ -81,13 +296,55 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         return Ok(());
     }
     
+    // Race mode merges pending feeds, the queued and block commands have none to race
+    if cli.race && !matches!(cli.command, None | Some(Commands::Pending | Commands::All)) {
+        return Err("--race only applies to the pending and all commands".into());
+    }
+    
     println!("{}", "ULTRETH - Ethereum High-Performance Node CLI".bright_green().bold());
     println!("{}", "----------------------------------------".bright_green());
-    println!("{} {}", "Connecting to:".yellow(), cli.endpoint);
-    println!("{} {} {}", "Rate limit:".yellow(), cli.rate_limit, "queries/second");
+    
+    // Collect endpoints from the command line and the optional endpoints file
+    let mut endpoints = cli.endpoint.clone();
+    if let Some(path) = &cli.endpoints_file {
+        endpoints.extend(read_endpoints_file(path)?);
+    }
+    if endpoints.is_empty() {
+        endpoints.push(DEFAULT_ENDPOINT.to_string());
+    }
+    
+    for endpoint in &endpoints {
+        println!("{} {}", "Connecting to:".yellow(), endpoint);
+    }
+    if cli.rate_limit > 0 {
+        println!("{} {} queries/second (burst {})", "Rate limit:".yellow(), cli.rate_limit,
+            cli.burst.unwrap_or(cli.rate_limit));
+    } else {
+        println!("{} unlimited", "Rate limit:".yellow());
+    }
+    
+    // Every RPC call is charged against a token bucket
+    let rate_limit = RateLimitConfig {
+        rate: cli.rate_limit,
+        burst: cli.burst.unwrap_or(cli.rate_limit),
+        weights: cli.method_weights.iter().cloned().collect(),
+        adaptive: cli.adaptive,
+    };
+    
+    // HTTP calls issued within the batch window share one request
+    let config = ConnectionConfig {
+        rate_limit,
+        batch: BatchConfig {
+            max_size: cli.batch_size,
+            window: Duration::from_millis(cli.batch_window_ms),
+        },
+    };
+    
+    // One bucket for every connection, race mode must not multiply the rate limit
+    let bucket = Arc::new(TokenBucket::new(&config.rate_limit));
     
     // Connect to Ethereum node
-    let provider = match connect_to_node(&cli.endpoint).await {
+    let provider = match connect_to_node(&endpoints, &config, Arc::clone(&bucket)).await {
         Ok(provider) => Arc::new(provider),
         Err(e) => {
             eprintln!("{} {}", "Connection Error:".bright_red().bold(), e);
@@ -102,23 +359,49 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             eprintln!("\nTip: Run {} to see all available public endpoints", "ultreth --list-endpoints".bright_cyan());
             eprintln!("Example: {} {}", "ultreth -e".bright_cyan(), "https://rpc.ankr.com/eth".bright_green());
             
-            // Check if the error contains specific error codes and provide targeted advice
-            let error_str = e.to_string();
-            if error_str.contains("-32046") || error_str.contains("Cannot fulfill request") {
-                eprintln!("{}", "\nSpecific Error Information:".bright_yellow());
-                eprintln!("The error code -32046 (Cannot fulfill request) typically indicates:");
-                eprintln!("- The endpoint is rate-limited and you've exceeded the allowed requests");
-                eprintln!("- The endpoint may require an API key for the requested method");
-                eprintln!("- The endpoint may be temporarily unavailable");
-                eprintln!("\nRecommendation: Try using Ankr's public endpoint which has higher rate limits");
+            // Provide targeted advice for the specific failure
+            match &e {
+                NodeError::RateLimited { code, .. } => {
+                    eprintln!("{}", "\nSpecific Error Information:".bright_yellow());
+                    eprintln!("The error code {} (Cannot fulfill request) typically indicates:", code);
+                    eprintln!("- The endpoint is rate-limited and you've exceeded the allowed requests");
+                    eprintln!("- The endpoint may require an API key for the requested method");
+                    eprintln!("- The endpoint may be temporarily unavailable");
+                    eprintln!("\nRecommendation: Try using Ankr's public endpoint which has higher rate limits");
+                }
+                NodeError::MethodNotFound(_) => {
+                    eprintln!("{}", "\nSpecific Error Information:".bright_yellow());
+                    eprintln!("The endpoint does not support a required JSON-RPC method (code: -32601)");
+                    eprintln!("\nRecommendation: Try a different endpoint with more comprehensive method support");
+                }
+                NodeError::Unauthorized(_) => {
+                    eprintln!("{}", "\nSpecific Error Information:".bright_yellow());
+                    eprintln!("The endpoint rejected the request, the API key is missing, invalid or lacks access");
+                    eprintln!("\nRecommendation: Check the API key in the endpoint URL");
+                }
+                NodeError::Timeout(_) => {
+                    eprintln!("{}", "\nSpecific Error Information:".bright_yellow());
+                    eprintln!("The endpoint did not respond in time, it may be unreachable or overloaded");
+                }
+                NodeError::InvalidUrl(_) => {
+                    eprintln!("{}", "\nSpecific Error Information:".bright_yellow());
+                    eprintln!("Endpoints must be http(s)://, ws(s):// URLs or the path of an IPC socket");
+                }
+                NodeError::Syncing(_) => {
+                    eprintln!("{}", "\nSpecific Error Information:".bright_yellow());
+                    eprintln!("The node is still syncing and cannot serve the latest chain state yet");
+                    eprintln!("\nRecommendation: Wait for the node to finish syncing or use another endpoint");
+                }
+                _ => {}
             }
-            return Err(e);
+            return Err(e.into());
         }
     };
     
     // Create channels for transaction and block data
     let (tx_sender, mut tx_receiver) = mpsc::channel(1000);
     let (block_sender, mut block_receiver) = mpsc::channel(100);
+    let (queued_sender, mut queued_receiver) = mpsc::channel(1000);
     
     // Create a clone for the main thread to use for display
     let provider_for_display = Arc::clone(&provider);
@@ -126,42 +409,52 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     // Determine which data streams to subscribe to based on command
     let command = cli.command.unwrap_or(Commands::All);
     
-    match command {
+    // Shared duplicate suppression for the pending feed
+    let seen_ttl = Duration::from_secs(cli.seen_ttl);
+    let seen = Arc::new(Mutex::new(SeenSet::new(cli.seen_capacity, seen_ttl)));
+    
+    // In race mode every endpoint gets its own connection and pending feed
+    let race_tracker = cli.race.then(|| Arc::new(Mutex::new(RaceTracker::new(&endpoints))));
+    
+    // Full blocks cost more calls and are only fetched for the analytics
+    let block_detail = if cli.full_blocks { BlockDetail::Full } else { BlockDetail::Hashes };
+    
+    // Duplicate suppression sets of every pending feed, checked to spot privately sent transactions
+    let mut pending_seen = vec![Arc::clone(&seen)];
+    
+    match &command {
         Commands::Pending => {
-            let provider_clone = Arc::clone(&provider);
-            let rate_limit = cli.rate_limit;
-            tokio::spawn(async move {
-                if let Err(e) = subscribe_to_pending_transactions(provider_clone, tx_sender, rate_limit).await {
-                    eprintln!("Error in pending transactions subscription: {}", e);
-                }
-            });
+            match &race_tracker {
+                Some(tracker) => pending_seen = spawn_race_feed(&endpoints, tx_sender, &config, &bucket, Arc::clone(tracker),
+                    || SeenSet::new(cli.seen_capacity, seen_ttl), cli.fetch_concurrency).await?,
+                None => spawn_pending_feed(Arc::clone(&provider), tx_sender, None, cli.rate_limit, Arc::clone(&seen),
+                    cli.fetch_concurrency),
+            }
+            spawn_block_feed(Arc::clone(&provider), block_sender, block_detail);
         },
-        Commands::Blocks => {
-            let provider_clone = Arc::clone(&provider);
-            tokio::spawn(async move {
-                if let Err(e) = subscribe_to_blocks(provider_clone, block_sender).await {
-                    eprintln!("Error in blocks subscription: {}", e);
-                }
-            });
+        Commands::Blocks { from: Some(from), to, checkpoint } => {
+            if to.is_some_and(|to| to < *from) {
+                return Err(format!("--to ({}) must not be below --from ({})", to.unwrap_or_default(), from).into());
+            }
+            let range = ScanRange { from: *from, to: *to, checkpoint: checkpoint.clone() };
+            spawn_backfill_feed(Arc::clone(&provider), block_sender, range, cli.fetch_concurrency, block_detail);
+        },
+        Commands::Blocks { from: None, .. } => {
+            spawn_block_feed(Arc::clone(&provider), block_sender, block_detail);
         },
         Commands::All => {
-            // Clone for pending transactions subscription
-            let provider_clone1 = Arc::clone(&provider);
-            let rate_limit = cli.rate_limit;
-            let tx_sender_clone = tx_sender.clone();
-            tokio::spawn(async move {
-                if let Err(e) = subscribe_to_pending_transactions(provider_clone1, tx_sender_clone, rate_limit).await {
-                    eprintln!("Error in pending transactions subscription: {}", e);
-                }
-            });
-            
-            // Clone for blocks subscription
-            let provider_clone2 = Arc::clone(&provider);
-            tokio::spawn(async move {
-                if let Err(e) = subscribe_to_blocks(provider_clone2, block_sender).await {
-                    eprintln!("Error in blocks subscription: {}", e);
-                }
-            });
+            match &race_tracker {
+                Some(tracker) => pending_seen = spawn_race_feed(&endpoints, tx_sender.clone(), &config,
+                    &bucket, Arc::clone(tracker), || SeenSet::new(cli.seen_capacity, seen_ttl), cli.fetch_concurrency).await?,
+                None => spawn_pending_feed(Arc::clone(&provider), tx_sender.clone(), None, cli.rate_limit, Arc::clone(&seen),
+                    cli.fetch_concurrency),
+            }
+            spawn_block_feed(Arc::clone(&provider), block_sender, block_detail);
+        },
+        Commands::Queued => {
+            spawn_pending_feed(Arc::clone(&provider), tx_sender, Some(queued_sender), cli.rate_limit, Arc::clone(&seen),
+                cli.fetch_concurrency);
+            spawn_block_feed(Arc::clone(&provider), block_sender, block_detail);
         },
     }
     
@@ -172,20 +465,146 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         let _ = interrupt_sender_clone.try_send(());
     })?;
     
+    // In queued mode only promotions are shown from the pending feed
+    let queued_only = matches!(command, Commands::Queued);
+    
+    // Blocks are always followed to track what gets mined, but only shown when asked for
+    let show_blocks = matches!(command, Commands::Blocks { .. } | Commands::All);
+    
+    // A closed block range ends the program once every block was shown
+    let scan_only = matches!(command, Commands::Blocks { to: Some(_), .. });
+    let mut blocks_closed = false;
+    
+    // Moved only once a block was shown, so an interrupted scan resumes with the first block not shown
+    let checkpoint = match &command {
+        Commands::Blocks { checkpoint, .. } => checkpoint.clone(),
+        _ => None,
+    };
+    
+    // Transaction types to show, empty shows every type
+    let mut shown_types: HashSet<u64> = cli.tx_types.iter().copied().collect();
+    if cli.blobs_only {
+        shown_types.insert(BLOB_TX_TYPE);
+    }
+    let type_shown = |tx: &Transaction| {
+        shown_types.is_empty() || shown_types.contains(&tx.transaction_type.map_or(0, |tx_type| tx_type.as_u64()))
+    };
+    let display_options = DisplayOptions {
+        known_delegates: cli.known_delegates.iter().copied().collect(),
+        max_fraction_digits: cli.decimals,
+    };
+    // Decimals and symbols of the tokens in transfers and approvals
+    let tokens = TokenCache::default();
+    
+    // Shared view of every observed transaction and its fate
+    let mempool = Arc::new(Mutex::new(Mempool::new(Duration::from_secs(cli.drop_after))));
+    
+    // Without a pending feed every mined transaction would look private. The feeds are running, so
+    // every transaction is either in the snapshot of the pool or delivered by them
+    let mut orderflow = match command {
+        Commands::Blocks { .. } => None,
+        _ => {
+            let snapshot = pool_snapshot(provider.as_ref()).await;
+            match &snapshot {
+                Some(snapshot) => println!("{} {} transactions already pending at block {}", "Pool snapshot:".yellow(),
+                    snapshot.hashes.len(), snapshot.head),
+                None => println!("{} private orderflow is reported after a {}s warmup (no txpool_content)",
+                    "Pool snapshot unavailable:".yellow(), WARMUP.as_secs()),
+            }
+            Some(OrderflowTracker::new(pending_seen, snapshot))
+        }
+    };
+    
+    // Blob base fees follow the blob schedule of the chain, the node reports them on other chains
+    let blob_fees = BlobFees::for_chain(provider.get_chainid().await.unwrap_or_default());
+    if !blob_fees.is_known() {
+        println!("{} taken from eth_blobBaseFee (no blob schedule for this chain)", "Blob base fee:".yellow());
+    }
+    
     // Main event loop
     let mut tx_count = 0;
     let mut block_count = 0;
+    let mut queued_count = 0;
+    let mut promoted_count = 0;
+    let mut reorg_count = 0;
+    let mut max_reorg_depth = 0;
+    let mut economics = BlockEconomics::default();
+    let mut access_lists = AccessListStats::default();
+    let mut fees = FeeContext::default();
+    let mut replacements: HashMap<ReplacementKind, u64> = HashMap::new();
     let start_time = Instant::now();
+    // Created once, a sleep recreated on every iteration never fires while transactions keep arriving
+    let mut tick = interval(Duration::from_secs(1));
     
     loop {
         tokio::select! {
-            Some(transaction) = tx_receiver.recv() => {
+            Some(pending) = tx_receiver.recv() => {
                 tx_count += 1;
-                let formatted = format_transaction(&transaction, &provider_for_display).await;
+                access_lists.record(&pending.tx);
+                let replacement = mempool.lock().unwrap().observe(&pending);
+                if let Some(replacement) = &replacement {
+                    *replacements.entry(replacement.kind).or_insert(0) += 1;
+                }
+                if matches!(pending.status, PoolStatus::Promoted { .. }) {
+                    promoted_count += 1;
+                } else if queued_only {
+                    continue;
+                }
+                if !type_shown(&pending.tx) {
+                    continue;
+                }
+                let formatted = format_transaction(&pending, replacement.as_ref(), &fees, &display_options,
+                    &tokens, &provider_for_display).await;
                 println!("{}", formatted);
             }
-            Some(block) = block_receiver.recv() => {
+            Some(queued) = queued_receiver.recv() => {
+                queued_count += 1;
+                access_lists.record(&queued.tx);
+                let replacement = mempool.lock().unwrap().observe(&queued);
+                if let Some(replacement) = &replacement {
+                    *replacements.entry(replacement.kind).or_insert(0) += 1;
+                }
+                if !type_shown(&queued.tx) {
+                    continue;
+                }
+                let formatted = format_transaction(&queued, replacement.as_ref(), &fees, &display_options,
+                    &tokens, &provider_for_display).await;
+                println!("{}", formatted);
+            }
+            event = block_receiver.recv(), if !blocks_closed => {
+                let Some(event) = event else {
+                    blocks_closed = true;
+                    if scan_only {
+                        let _ = interrupt_sender.try_send(());
+                    }
+                    continue;
+                };
+                let NewBlock { block, analytics } = match event {
+                    BlockEvent::New(new_block) => *new_block,
+                    BlockEvent::Reorg(reorg) => {
+                        reorg_count += 1;
+                        max_reorg_depth = max_reorg_depth.max(reorg.depth());
+                        mempool.lock().unwrap().on_reorg(&reorg);
+                        println!("{} depth {}, common ancestor {}", "Chain Reorg:".bright_red().bold(), reorg.depth(),
+                            reorg.common_ancestor);
+                        for hash in &reorg.orphaned {
+                            println!("  {} {:?}", "Orphaned:".red(), hash);
+                        }
+                        println!("{}", "----------------------------------------".bright_red());
+                        continue;
+                    }
+                };
                 block_count += 1;
+                mempool.lock().unwrap().on_block(&block);
+                let blob_base_fee = blob_fees.base_fee(provider.as_ref(), &block).await;
+                fees.update(&block, blob_base_fee);
+                if let Some(analytics) = &analytics {
+                    economics.record(analytics);
+                }
+                let private = orderflow.as_mut().and_then(|orderflow| orderflow.on_block(&block, &mempool.lock().unwrap()));
+                if !show_blocks {
+                    continue;
+                }
                 println!("{} {}", "New Block:".bright_blue().bold(), block.number.unwrap());
                 println!("{} {}", "Hash:".cyan(), block.hash.unwrap());
                 println!("{} {}", "Parent Hash:".cyan(), block.parent_hash);
@@ -193,7 +612,30 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                 println!("{} {}", "Gas Used:".cyan(), block.gas_used);
                 println!("{} {}", "Gas Limit:".cyan(), block.gas_limit);
                 println!("{} {}", "Timestamp:".cyan(), block.timestamp);
+                if let Some(blob_gas_used) = block.blob_gas_used {
+                    println!("{} {} ({} blobs)", "Blob Gas Used:".cyan(), blob_gas_used, blob_count(blob_gas_used));
+                }
+                if let Some((excess_blob_gas, blob_base_fee)) = block.excess_blob_gas.zip(blob_base_fee) {
+                    println!("{} {} (excess blob gas {})", "Blob Base Fee:".cyan(),
+                        format_gwei(blob_base_fee, cli.decimals), excess_blob_gas);
+                }
+                if let Some(analytics) = &analytics {
+                    print!("{}", format_block_analytics(analytics, cli.decimals));
+                }
+                if let Some(private) = private {
+                    println!("{} {:?}", "Fee Recipient:".cyan(), private.fee_recipient);
+                    println!("{} {}/{} ({:.1}%) never seen as pending", "Private Orderflow:".cyan(),
+                        private.private.len(), private.total, private.private_ratio() * 100.0);
+                    for hash in &private.private {
+                        println!("  {:?}", hash);
+                    }
+                }
                 println!("{}", "----------------------------------------".bright_blue());
+                if let (Some(path), Some(number)) = (&checkpoint, block.number) {
+                    if let Err(e) = write_checkpoint(path, number.as_u64()) {
+                        eprintln!("{} {}", "Checkpoint Error:".bright_red(), e);
+                    }
+                }
             }
             Some(_) = interrupt_receiver.recv() => {
                 println!("{}", "\nShutting down...".bright_yellow());
@@ -204,14 +646,93 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                     println!("{} {} ({} per second)", "Total blocks processed:".yellow(), 
                         block_count, block_count as f64 / elapsed as f64);
                 }
+                if access_lists.transactions > 0 {
+                    println!("{} {}/{} transactions ({:.1}%), {} addresses, {} storage keys, {} with unrelated addresses",
+                        "Access lists:".yellow(), access_lists.with_access_list, access_lists.transactions,
+                        access_lists.usage_percent(), access_lists.addresses, access_lists.storage_keys,
+                        access_lists.with_unrelated);
+                }
+                if economics.blocks > 0 {
+                    let types = &economics.tx_types;
+                    println!("{} {} blocks, {} ETH burned, {} ETH priority fees", "Block economics:".yellow(),
+                        economics.blocks, format_eth(economics.burned, cli.decimals), format_eth(economics.priority_fees, cli.decimals));
+                    println!("  {} {} legacy, {} access list, {} EIP-1559, {} blob, {} set-code, {} other",
+                        "Transaction types:".cyan(), types.legacy, types.access_list, types.dynamic_fee, types.blob,
+                        types.set_code, types.other);
+                }
+                if reorg_count > 0 {
+                    println!("{} {} (max depth {})", "Chain reorgs:".yellow(), reorg_count, max_reorg_depth);
+                }
+                if queued_only {
+                    println!("{} {} ({} promoted to pending)", "Total queued transactions:".yellow(),
+                        queued_count, promoted_count);
+                }
+                if !matches!(command, Commands::Blocks { .. }) {
+                    let mempool = mempool.lock().unwrap();
+                    let stats = mempool.stats();
+                    println!("{} {} pending, {} queued, {} replaced, {} mined, {} dropped", "Mempool:".yellow(),
+                        stats.pending, stats.queued, stats.replaced, stats.mined, stats.dropped);
+                    let inclusion = mempool.inclusion();
+                    println!("{} {} finished: {} mined, {} replaced, {} dropped", "Inclusion:".yellow(),
+                        inclusion.total(), inclusion.mined, inclusion.replaced, inclusion.dropped);
+                    if let Some(latency) = inclusion.latency_summary() {
+                        println!("  {} min {:.1}s, p50 {:.1}s, p90 {:.1}s, p99 {:.1}s, max {:.1}s, mean {:.1}s ({} samples)",
+                            "Time to mine:".cyan(), latency.min.as_secs_f64(), latency.p50.as_secs_f64(),
+                            latency.p90.as_secs_f64(), latency.p99.as_secs_f64(), latency.max.as_secs_f64(),
+                            latency.mean.as_secs_f64(), latency.samples);
+                    }
+                    let count = |kind| replacements.get(&kind).copied().unwrap_or(0);
+                    println!("{} {} speed-ups, {} cancellations, {} other replacements", "Replacements:".yellow(),
+                        count(ReplacementKind::SpeedUp), count(ReplacementKind::Cancel),
+                        count(ReplacementKind::Replacement));
+                }
+                if let Some(orderflow) = &orderflow {
+                    let total = orderflow.total();
+                    println!("{} {}/{} mined transactions ({:.1}%) never seen as pending, over {} blocks",
+                        "Private orderflow:".yellow(), total.private, total.transactions,
+                        total.private_ratio() * 100.0, total.blocks);
+                    for stats in orderflow.summary().iter().take(10) {
+                        println!("  {} blocks: {}, private: {}/{} ({:.1}%)",
+                            format!("{:?}", stats.fee_recipient).bright_cyan(), stats.blocks, stats.private,
+                            stats.transactions, stats.private_ratio() * 100.0);
+                    }
+                }
+                if race_tracker.is_none() {
+                    let seen = seen.lock().unwrap();
+                    println!("{} {} ({} distinct transactions)", "Duplicates suppressed:".yellow(),
+                        seen.duplicates(), seen.inserted());
+                }
+                if let Some(tracker) = &race_tracker {
+                    let tracker = tracker.lock().unwrap();
+                    println!("{} {}", "First-seen race (distinct transactions):".yellow(), tracker.total());
+                    for (index, stats) in tracker.summary().iter().enumerate() {
+                        let median = tracker.median_delta(index).unwrap_or_default();
+                        println!("  {} first: {} ({:.1}%), seen: {}, avg delay when late: {:.1} ms, median delta: {:.1} ms",
+                            stats.endpoint.bright_cyan(), stats.wins, stats.win_rate(tracker.total()) * 100.0,
+                            stats.seen, stats.average_delay().as_secs_f64() * 1000.0, median.as_secs_f64() * 1000.0);
+                    }
+                }
+                println!("{}", "Endpoint health:".yellow());
+                for (url, health) in (*provider).as_ref().inner().health() {
+                    println!("  {} requests: {}, errors: {} ({:.1}%), rate-limited: {}, latency: {:.1} ms",
+                        url.bright_cyan(), health.requests, health.errors, health.error_rate * 100.0,
+                        health.rate_limited, health.latency_ms);
+                }
                 break;
             }
-            _ = sleep(Duration::from_secs(1)) => {
+            _ = tick.tick() => {
+                mempool.lock().unwrap().prune();
                 let rate = calculate_query_rate(tx_count, start_time.elapsed());
                 if tx_count > 0 {
                     println!("{} {:.2} {}", "Current query rate:".bright_cyan(), 
                         rate, "queries/second".bright_cyan());
                 }
+                let bucket = (*provider).as_ref().bucket();
+                if bucket.is_adaptive() {
+                    println!("{} {:.1}/{} {} ({} throttle events)", "Adaptive rate limit:".bright_cyan(),
+                        bucket.current_rate(), cli.rate_limit, "queries/second".bright_cyan(),
+                        bucket.throttle_events());
+                }
             }
         }
     }
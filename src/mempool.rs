@@ -0,0 +1,489 @@
+use ethers::prelude::*;
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::time::{Duration, Instant};
+
+use crate::chain::Reorg;
+use crate::inclusion::InclusionStats;
+use crate::txpool::{PendingTx, PoolStatus};
+
+/// Maximum number of transactions tracked, the oldest are evicted first
+const MAX_TRACKED: usize = 200_000;
+
+/// How long replaced, mined and dropped transactions stay queryable
+const RETENTION: Duration = Duration::from_secs(10 * 60);
+
+/// Fee bump in percent nodes require to accept a replacement (geth's default `txpool.pricebump`)
+pub const MIN_PRICE_BUMP: f64 = 10.0;
+
+/// Lifecycle state of an observed transaction
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TxState {
+    Pending,
+    Queued,
+    /// Another transaction with the same sender and nonce took its place
+    Replaced { by: H256 },
+    /// Included at `position` in `block`, `latency` after it was first seen
+    Mined { block: U64, position: usize, latency: Duration },
+    /// Evicted from the pool, or its nonce was used by another mined transaction
+    Dropped,
+}
+
+impl TxState {
+    /// Whether the transaction can still be mined
+    pub fn is_live(&self) -> bool {
+        matches!(self, TxState::Pending | TxState::Queued)
+    }
+}
+
+/// A transaction and what became of it
+#[derive(Debug, Clone)]
+pub struct TrackedTx {
+    pub tx: Transaction,
+    pub state: TxState,
+    pub first_seen: Instant,
+    /// Time of the last state change
+    pub updated_at: Instant,
+}
+
+/// How a transaction relates to the one it replaced
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ReplacementKind {
+    /// Same recipient, value and input, only the fees changed
+    SpeedUp,
+    /// Zero-value self-send that voids the replaced transaction
+    Cancel,
+    /// Recipient, value or input changed
+    Replacement,
+}
+
+impl std::fmt::Display for ReplacementKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ReplacementKind::SpeedUp => write!(f, "SPEED-UP"),
+            ReplacementKind::Cancel => write!(f, "CANCEL"),
+            ReplacementKind::Replacement => write!(f, "REPLACEMENT"),
+        }
+    }
+}
+
+/// A transaction taking over the sender/nonce slot of an earlier one
+#[derive(Debug, Clone)]
+pub struct Replacement {
+    pub kind: ReplacementKind,
+    /// The transaction that was replaced
+    pub replaced: Transaction,
+    /// Change of the gas price, or of the max fee per gas for EIP-1559 transactions, in percent
+    pub fee_bump: Option<f64>,
+    /// Change of the max priority fee per gas in percent, if both transactions set one
+    pub tip_bump: Option<f64>,
+    /// Fee cap or tip raised by less than [`MIN_PRICE_BUMP`], nodes with default settings reject it
+    pub below_min_bump: bool,
+}
+
+impl Replacement {
+    /// Classify `new` as a replacement of `old`, both with the same sender and nonce
+    pub fn classify(old: &Transaction, new: &Transaction) -> Self {
+        let kind = if new.to == Some(new.from) && new.value.is_zero() {
+            ReplacementKind::Cancel
+        } else if new.to == old.to && new.value == old.value && new.input == old.input {
+            ReplacementKind::SpeedUp
+        } else {
+            ReplacementKind::Replacement
+        };
+
+        let fee = |tx: &Transaction| tx.max_fee_per_gas.or(tx.gas_price);
+        // A legacy gas price is both the fee cap and the tip
+        let tip = |tx: &Transaction| tx.max_priority_fee_per_gas.or(tx.gas_price);
+        let bump = |fee: &dyn Fn(&Transaction) -> Option<U256>| {
+            fee(old).zip(fee(new)).and_then(|(old, new)| bump_percent(old, new))
+        };
+        let fee_bump = bump(&fee);
+        Replacement {
+            kind,
+            replaced: old.clone(),
+            fee_bump,
+            tip_bump: old
+                .max_priority_fee_per_gas
+                .zip(new.max_priority_fee_per_gas)
+                .and_then(|(old, new)| bump_percent(old, new)),
+            below_min_bump: [fee_bump, bump(&tip)].iter().any(|pct| pct.is_some_and(|pct| pct < MIN_PRICE_BUMP)),
+        }
+    }
+}
+
+/// Relative change from `old` to `new` in percent, with two decimals of precision
+fn bump_percent(old: U256, new: U256) -> Option<f64> {
+    if old.is_zero() {
+        return None;
+    }
+    let basis_points = |delta: U256| {
+        let bps = delta.checked_mul(U256::from(10_000))? / old;
+        u64::try_from(bps).ok().map(|bps| bps as f64 / 100.0)
+    };
+    if new >= old {
+        basis_points(new - old)
+    } else {
+        basis_points(old - new).map(|pct| -pct)
+    }
+}
+
+/// Number of tracked transactions in each state
+#[derive(Debug, Clone, Default)]
+pub struct MempoolStats {
+    pub pending: usize,
+    pub queued: usize,
+    pub replaced: usize,
+    pub mined: usize,
+    pub dropped: usize,
+}
+
+/// In-memory view of the mempool keyed by sender and nonce
+///
+/// Fed with every pending and queued transaction and every new block. Each sender/nonce slot holds
+/// at most one live transaction, a newer transaction for the same slot replaces the older one.
+/// Live transactions neither mined nor replaced within `drop_after` are considered dropped.
+#[derive(Debug)]
+pub struct Mempool {
+    txs: HashMap<H256, TrackedTx>,
+    /// Live transaction of each sender, by nonce
+    by_sender: HashMap<Address, BTreeMap<U256, H256>>,
+    /// Insertion order of `txs`, used to evict the oldest entries
+    order: VecDeque<H256>,
+    drop_after: Duration,
+    /// Outcomes of all transactions that left the pool, including those no longer tracked
+    inclusion: InclusionStats,
+}
+
+impl Mempool {
+    pub fn new(drop_after: Duration) -> Self {
+        Mempool {
+            txs: HashMap::new(),
+            by_sender: HashMap::new(),
+            order: VecDeque::new(),
+            drop_after,
+            inclusion: InclusionStats::default(),
+        }
+    }
+
+    /// Record a transaction from the pending or queued feed
+    ///
+    /// Returns the replacement details if the sender already had a different live transaction with
+    /// the same nonce.
+    pub fn observe(&mut self, pending: &PendingTx) -> Option<Replacement> {
+        let now = Instant::now();
+        let tx = &pending.tx;
+        let state = match pending.status {
+            PoolStatus::Queued(_) => TxState::Queued,
+            PoolStatus::Pending | PoolStatus::Promoted { .. } => TxState::Pending,
+        };
+
+        if let Some(tracked) = self.txs.get_mut(&tx.hash) {
+            // Known transaction, e.g. promoted from queued to pending
+            if tracked.state.is_live() && tracked.state != state {
+                tracked.state = state;
+                tracked.updated_at = now;
+            }
+            return None;
+        }
+
+        let slots = self.by_sender.entry(tx.from).or_default();
+        let replaced = slots.insert(tx.nonce, tx.hash).and_then(|previous| {
+            let tracked = self.txs.get_mut(&previous)?;
+            if tracked.state.is_live() {
+                self.inclusion.replaced += 1;
+            }
+            tracked.state = TxState::Replaced { by: tx.hash };
+            tracked.updated_at = now;
+            Some(Replacement::classify(&tracked.tx, tx))
+        });
+
+        self.txs.insert(tx.hash, TrackedTx {
+            tx: tx.clone(),
+            state,
+            first_seen: pending.seen_at,
+            updated_at: now,
+        });
+        self.order.push_back(tx.hash);
+        if self.order.len() > MAX_TRACKED {
+            if let Some(oldest) = self.order.pop_front() {
+                self.forget(&oldest);
+            }
+        }
+
+        replaced
+    }
+
+    /// Mark the transactions of a new block as mined, recording their position and time-to-mine
+    ///
+    /// Live transactions whose sender/nonce slot was used by a mined transaction are dropped.
+    pub fn on_block(&mut self, block: &Block<TxHash>) {
+        let Some(number) = block.number else {
+            return;
+        };
+        let now = Instant::now();
+
+        for (position, hash) in block.transactions.iter().enumerate() {
+            let Some(tracked) = self.txs.get_mut(hash) else {
+                continue;
+            };
+            match tracked.state {
+                TxState::Mined { .. } => continue,
+                // The replacement or the drop did not stick after all
+                TxState::Replaced { .. } => self.inclusion.replaced -= 1,
+                TxState::Dropped => self.inclusion.dropped -= 1,
+                TxState::Pending | TxState::Queued => {}
+            }
+            let latency = now.duration_since(tracked.first_seen);
+            self.inclusion.record_mined(latency);
+            tracked.state = TxState::Mined { block: number, position, latency };
+            tracked.updated_at = now;
+            let (from, nonce) = (tracked.tx.from, tracked.tx.nonce);
+
+            // Every other transaction of the sender up to this nonce can no longer be mined
+            let Some(slots) = self.by_sender.get_mut(&from) else {
+                continue;
+            };
+            let stale: Vec<H256> = slots.range(..=nonce).map(|(_, hash)| *hash).collect();
+            slots.retain(|slot_nonce, _| *slot_nonce > nonce);
+            if slots.is_empty() {
+                self.by_sender.remove(&from);
+            }
+            for stale_hash in stale.into_iter().filter(|stale_hash| stale_hash != hash) {
+                if let Some(stale_tx) = self.txs.get_mut(&stale_hash) {
+                    if stale_tx.state.is_live() {
+                        self.inclusion.dropped += 1;
+                        stale_tx.state = TxState::Dropped;
+                        stale_tx.updated_at = now;
+                    }
+                }
+            }
+        }
+    }
+
+    /// Return the transactions mined above the common ancestor of a reorg to the pending state
+    ///
+    /// Transactions that made it into the new branch are marked mined again by its blocks.
+    pub fn on_reorg(&mut self, reorg: &Reorg) {
+        let now = Instant::now();
+        for (hash, tracked) in self.txs.iter_mut() {
+            let TxState::Mined { block, latency, .. } = tracked.state else {
+                continue;
+            };
+            if block <= reorg.common_ancestor {
+                continue;
+            }
+            self.inclusion.record_unmined(latency);
+            tracked.state = TxState::Pending;
+            tracked.updated_at = now;
+            self.by_sender.entry(tracked.tx.from).or_default().entry(tracked.tx.nonce).or_insert(*hash);
+        }
+    }
+
+    /// Drop live transactions that went stale and forget finished ones past their retention
+    pub fn prune(&mut self) {
+        let now = Instant::now();
+        let mut expired = Vec::new();
+
+        for (hash, tracked) in self.txs.iter_mut() {
+            let age = now.duration_since(tracked.updated_at);
+            if tracked.state.is_live() {
+                if age >= self.drop_after {
+                    self.inclusion.dropped += 1;
+                    tracked.state = TxState::Dropped;
+                    tracked.updated_at = now;
+                    if let Some(slots) = self.by_sender.get_mut(&tracked.tx.from) {
+                        if slots.get(&tracked.tx.nonce) == Some(hash) {
+                            slots.remove(&tracked.tx.nonce);
+                        }
+                    }
+                }
+            } else if age >= RETENTION {
+                expired.push(*hash);
+            }
+        }
+
+        for hash in &expired {
+            self.txs.remove(hash);
+        }
+        self.by_sender.retain(|_, slots| !slots.is_empty());
+        if !expired.is_empty() {
+            self.order.retain(|hash| self.txs.contains_key(hash));
+        }
+    }
+
+    /// Look up a tracked transaction by hash
+    pub fn get(&self, hash: &H256) -> Option<&TrackedTx> {
+        self.txs.get(hash)
+    }
+
+    /// Live transactions of a sender, ordered by nonce
+    #[allow(dead_code)]
+    pub fn sender_txs(&self, sender: &Address) -> Vec<&TrackedTx> {
+        self.by_sender
+            .get(sender)
+            .map(|slots| slots.values().filter_map(|hash| self.txs.get(hash)).collect())
+            .unwrap_or_default()
+    }
+
+    /// Number of tracked transactions in each state
+    pub fn stats(&self) -> MempoolStats {
+        let mut stats = MempoolStats::default();
+        for tracked in self.txs.values() {
+            match tracked.state {
+                TxState::Pending => stats.pending += 1,
+                TxState::Queued => stats.queued += 1,
+                TxState::Replaced { .. } => stats.replaced += 1,
+                TxState::Mined { .. } => stats.mined += 1,
+                TxState::Dropped => stats.dropped += 1,
+            }
+        }
+        stats
+    }
+
+    /// Final outcomes and time-to-mine distribution of every transaction seen leaving the pool
+    pub fn inclusion(&self) -> &InclusionStats {
+        &self.inclusion
+    }
+
+    /// Remove a transaction entirely, including its sender/nonce slot
+    fn forget(&mut self, hash: &H256) {
+        let Some(tracked) = self.txs.remove(hash) else {
+            return;
+        };
+        if let Some(slots) = self.by_sender.get_mut(&tracked.tx.from) {
+            if slots.get(&tracked.tx.nonce) == Some(hash) {
+                slots.remove(&tracked.tx.nonce);
+            }
+            if slots.is_empty() {
+                self.by_sender.remove(&tracked.tx.from);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn gwei(amount: u64) -> U256 {
+        U256::from(amount) * U256::exp10(9)
+    }
+
+    fn legacy_tx(gas_price: u64) -> Transaction {
+        Transaction {
+            from: Address::repeat_byte(0x11),
+            to: Some(Address::repeat_byte(0x22)),
+            value: U256::exp10(18),
+            gas_price: Some(gwei(gas_price)),
+            ..Default::default()
+        }
+    }
+
+    fn eip1559_tx(max_fee: u64, tip: u64) -> Transaction {
+        Transaction {
+            transaction_type: Some(2.into()),
+            max_fee_per_gas: Some(gwei(max_fee)),
+            max_priority_fee_per_gas: Some(gwei(tip)),
+            ..legacy_tx(max_fee)
+        }
+    }
+
+    #[test]
+    fn classifies_speed_ups_cancels_and_replacements() {
+        let old = eip1559_tx(100, 2);
+        assert_eq!(Replacement::classify(&old, &eip1559_tx(120, 3)).kind, ReplacementKind::SpeedUp);
+
+        let cancel = Transaction { to: Some(old.from), value: U256::zero(), ..eip1559_tx(120, 3) };
+        assert_eq!(Replacement::classify(&old, &cancel).kind, ReplacementKind::Cancel);
+
+        let other_input = Transaction { input: vec![0xde, 0xad].into(), ..eip1559_tx(120, 3) };
+        assert_eq!(Replacement::classify(&old, &other_input).kind, ReplacementKind::Replacement);
+        let other_value = Transaction { value: U256::one(), ..eip1559_tx(120, 3) };
+        assert_eq!(Replacement::classify(&old, &other_value).kind, ReplacementKind::Replacement);
+    }
+
+    #[test]
+    fn computes_bumps_in_percent() {
+        assert_eq!(bump_percent(gwei(100), gwei(110)), Some(10.0));
+        assert_eq!(bump_percent(U256::from(3), U256::from(4)), Some(33.33));
+        assert_eq!(bump_percent(gwei(100), gwei(90)), Some(-10.0));
+        assert_eq!(bump_percent(U256::zero(), gwei(1)), None);
+    }
+
+    #[test]
+    fn requires_a_ten_percent_bump_of_fee_cap_and_tip() {
+        let old = eip1559_tx(100, 10);
+        let exact = Replacement::classify(&old, &eip1559_tx(110, 11));
+        assert_eq!((exact.fee_bump, exact.tip_bump), (Some(10.0), Some(10.0)));
+        assert!(!exact.below_min_bump);
+
+        assert!(Replacement::classify(&old, &eip1559_tx(109, 11)).below_min_bump);
+        // A higher fee cap does not make up for a tip that was not bumped
+        assert!(Replacement::classify(&old, &eip1559_tx(200, 10)).below_min_bump);
+    }
+
+    #[test]
+    fn compares_a_legacy_gas_price_with_the_eip1559_fees() {
+        let old = legacy_tx(100);
+        let replacement = Replacement::classify(&old, &eip1559_tx(120, 110));
+        assert_eq!(replacement.kind, ReplacementKind::SpeedUp);
+        assert_eq!(replacement.fee_bump, Some(20.0));
+        assert_eq!(replacement.tip_bump, None);
+        assert!(!replacement.below_min_bump);
+
+        // The legacy gas price also counts as the tip to beat
+        assert!(Replacement::classify(&old, &eip1559_tx(120, 2)).below_min_bump);
+    }
+
+    fn block(number: u64, transactions: Vec<H256>) -> Block<TxHash> {
+        Block { number: Some(number.into()), transactions, ..Default::default() }
+    }
+
+    fn observed(mempool: &mut Mempool, hash: u8, nonce: u64) -> H256 {
+        let tx = Transaction { hash: H256::repeat_byte(hash), nonce: nonce.into(), ..legacy_tx(100) };
+        mempool.observe(&PendingTx::new(tx.clone()));
+        tx.hash
+    }
+
+    #[test]
+    fn drops_transactions_pending_for_longer_than_drop_after() {
+        let mut mempool = Mempool::new(Duration::from_millis(50));
+        let hash = observed(&mut mempool, 1, 0);
+
+        mempool.prune();
+        assert_eq!(mempool.get(&hash).unwrap().state, TxState::Pending);
+
+        std::thread::sleep(Duration::from_millis(60));
+        mempool.prune();
+        assert_eq!(mempool.get(&hash).unwrap().state, TxState::Dropped);
+        assert_eq!(mempool.inclusion().dropped, 1);
+        assert!(mempool.sender_txs(&Address::repeat_byte(0x11)).is_empty());
+    }
+
+    #[test]
+    fn moves_transactions_between_mined_and_pending() {
+        let mut mempool = Mempool::new(Duration::from_secs(60));
+        let mined = observed(&mut mempool, 1, 0);
+        // Same slot as the mined transaction, it can no longer be included
+        let stale = observed(&mut mempool, 2, 0);
+        let later = observed(&mut mempool, 3, 1);
+        assert_eq!(mempool.get(&mined).unwrap().state, TxState::Replaced { by: stale });
+
+        mempool.on_block(&block(100, vec![H256::repeat_byte(0xff), mined]));
+        assert!(matches!(mempool.get(&mined).unwrap().state, TxState::Mined { block, position: 1, .. } if block == 100.into()));
+        assert_eq!(mempool.get(&stale).unwrap().state, TxState::Dropped);
+        assert_eq!(mempool.get(&later).unwrap().state, TxState::Pending);
+        let inclusion = mempool.inclusion();
+        assert_eq!((inclusion.mined, inclusion.replaced, inclusion.dropped), (1, 0, 1));
+
+        mempool.on_reorg(&Reorg { common_ancestor: 99.into(), orphaned: vec![H256::repeat_byte(0xaa)] });
+        assert_eq!(mempool.get(&mined).unwrap().state, TxState::Pending);
+        assert_eq!(mempool.inclusion().mined, 0);
+        assert!(mempool.inclusion().latency_summary().is_none());
+
+        // Included again on the new branch
+        mempool.on_block(&block(100, vec![mined]));
+        assert!(matches!(mempool.get(&mined).unwrap().state, TxState::Mined { position: 0, .. }));
+        assert_eq!(mempool.inclusion().mined, 1);
+    }
+}
@@ -0,0 +1,196 @@
+use ethers::prelude::*;
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::mempool::Mempool;
+use crate::seen::SeenSet;
+
+/// Time after startup during which blocks are skipped if the pool could not be snapshotted, their
+/// transactions may have entered the mempool before we listened
+pub const WARMUP: Duration = Duration::from_secs(120);
+
+/// Transactions in the node's pool at startup
+#[derive(Debug, Clone, Default)]
+pub struct PoolSnapshot {
+    pub hashes: HashSet<H256>,
+    /// Head when the snapshot was taken, the transactions of blocks up to it left the pool before
+    pub head: U64,
+}
+
+/// Included transactions of one block that were never observed as pending
+#[derive(Debug, Clone)]
+pub struct BlockOrderflow {
+    pub fee_recipient: Address,
+    pub total: usize,
+    /// Likely private or bundle orderflow
+    pub private: Vec<H256>,
+}
+
+impl BlockOrderflow {
+    /// Share of the block's transactions that were never seen as pending
+    pub fn private_ratio(&self) -> f64 {
+        if self.total == 0 {
+            0.0
+        } else {
+            self.private.len() as f64 / self.total as f64
+        }
+    }
+}
+
+/// Private orderflow of all analysed blocks built by one fee recipient
+#[derive(Debug, Clone, Default)]
+pub struct FeeRecipientStats {
+    pub fee_recipient: Address,
+    pub blocks: u64,
+    pub transactions: u64,
+    pub private: u64,
+}
+
+impl FeeRecipientStats {
+    /// Share of the recipient's included transactions that were never seen as pending
+    pub fn private_ratio(&self) -> f64 {
+        if self.transactions == 0 {
+            0.0
+        } else {
+            self.private as f64 / self.transactions as f64
+        }
+    }
+}
+
+/// Flags mined transactions that never showed up in the public mempool
+///
+/// Each block is checked against the mempool, which keeps every fetched transaction until long
+/// after it was mined or dropped, and the duplicate suppression sets of the pending feeds, which
+/// hold the hashes mined before they could be fetched. Subscriptions never deliver transactions
+/// that were pending before startup, those are covered by a snapshot of the node's pool or, if
+/// the endpoint does not expose it, by skipping the blocks of the first [`WARMUP`]. Blocks mined
+/// before the snapshot are skipped.
+#[derive(Debug)]
+pub struct OrderflowTracker {
+    seen: Vec<Arc<Mutex<SeenSet>>>,
+    snapshot: PoolSnapshot,
+    /// End of the warmup, only set without a snapshot
+    warmup_until: Option<Instant>,
+    by_recipient: HashMap<Address, FeeRecipientStats>,
+}
+
+impl OrderflowTracker {
+    pub fn new(seen: Vec<Arc<Mutex<SeenSet>>>, snapshot: Option<PoolSnapshot>) -> Self {
+        OrderflowTracker {
+            seen,
+            warmup_until: snapshot.is_none().then(|| Instant::now() + WARMUP),
+            snapshot: snapshot.unwrap_or_default(),
+            by_recipient: HashMap::new(),
+        }
+    }
+
+    /// Classify the transactions of a new block, `None` while warming up or for blocks mined before the snapshot
+    pub fn on_block(&mut self, block: &Block<TxHash>, mempool: &Mempool) -> Option<BlockOrderflow> {
+        if block.number? <= self.snapshot.head || self.warmup_until.is_some_and(|until| Instant::now() < until) {
+            return None;
+        }
+
+        let seen: Vec<_> = self.seen.iter().map(|seen| seen.lock().unwrap()).collect();
+        let private: Vec<H256> = block
+            .transactions
+            .iter()
+            .filter(|hash| {
+                !self.snapshot.hashes.contains(hash) && mempool.get(hash).is_none() && !seen.iter().any(|seen| seen.contains(hash))
+            })
+            .copied()
+            .collect();
+        drop(seen);
+
+        let fee_recipient = block.author.unwrap_or_default();
+        let stats = self.by_recipient.entry(fee_recipient).or_insert_with(|| FeeRecipientStats {
+            fee_recipient,
+            ..Default::default()
+        });
+        stats.blocks += 1;
+        stats.transactions += block.transactions.len() as u64;
+        stats.private += private.len() as u64;
+
+        Some(BlockOrderflow {
+            fee_recipient,
+            total: block.transactions.len(),
+            private,
+        })
+    }
+
+    /// Totals over every analysed block
+    pub fn total(&self) -> FeeRecipientStats {
+        self.by_recipient.values().fold(FeeRecipientStats::default(), |mut total, stats| {
+            total.blocks += stats.blocks;
+            total.transactions += stats.transactions;
+            total.private += stats.private;
+            total
+        })
+    }
+
+    /// Per fee recipient breakdown, most blocks first
+    pub fn summary(&self) -> Vec<FeeRecipientStats> {
+        let mut summary: Vec<FeeRecipientStats> = self.by_recipient.values().cloned().collect();
+        summary.sort_by(|a, b| b.blocks.cmp(&a.blocks).then(b.private.cmp(&a.private)));
+        summary
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::txpool::PendingTx;
+
+    fn block(number: u64, transactions: Vec<H256>) -> Block<TxHash> {
+        Block {
+            number: Some(U64::from(number)),
+            author: Some(Address::repeat_byte(0xfe)),
+            transactions,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn flags_only_transactions_never_observed() {
+        let seen = Arc::new(Mutex::new(SeenSet::new(100, Duration::from_secs(60))));
+        let delivered = H256::repeat_byte(1);
+        seen.lock().unwrap().insert(delivered);
+        // Fetched long ago, its hash has since been forgotten by the seen set
+        let waiting = H256::repeat_byte(2);
+        let mut mempool = Mempool::new(Duration::from_secs(60));
+        mempool.observe(&PendingTx::new(Transaction { hash: waiting, ..Default::default() }));
+        let preexisting = H256::repeat_byte(3);
+        let private = H256::repeat_byte(4);
+        let snapshot = PoolSnapshot { hashes: HashSet::from([preexisting]), head: U64::from(10) };
+        let mut tracker = OrderflowTracker::new(vec![seen], Some(snapshot));
+
+        let orderflow = tracker.on_block(&block(11, vec![delivered, waiting, preexisting, private]), &mempool).unwrap();
+
+        assert_eq!(orderflow.private, vec![private]);
+        assert_eq!(orderflow.total, 4);
+        assert_eq!(tracker.total().private, 1);
+    }
+
+    #[test]
+    fn warms_up_without_a_pool_snapshot() {
+        let mempool = Mempool::new(Duration::from_secs(60));
+        let mut tracker = OrderflowTracker::new(Vec::new(), None);
+
+        assert!(tracker.on_block(&block(1, vec![H256::repeat_byte(1)]), &mempool).is_none());
+        assert_eq!(tracker.total().blocks, 0);
+    }
+
+    #[test]
+    fn skips_blocks_mined_before_the_snapshot() {
+        let mempool = Mempool::new(Duration::from_secs(60));
+        let snapshot = PoolSnapshot { hashes: HashSet::new(), head: U64::from(10) };
+        let mut tracker = OrderflowTracker::new(Vec::new(), Some(snapshot));
+
+        // The current head is delivered first, its transactions were never in the snapshot
+        assert!(tracker.on_block(&block(10, vec![H256::repeat_byte(1)]), &mempool).is_none());
+        assert!(tracker.on_block(&block(9, vec![H256::repeat_byte(2)]), &mempool).is_none());
+        let orderflow = tracker.on_block(&block(11, vec![H256::repeat_byte(3)]), &mempool).unwrap();
+        assert_eq!(orderflow.private, vec![H256::repeat_byte(3)]);
+        assert_eq!(tracker.total().blocks, 1);
+    }
+}
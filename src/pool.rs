@@ -0,0 +1,387 @@
+use async_trait::async_trait;
+use ethers::prelude::*;
+use ethers::providers::{JsonRpcClient, PubsubClient};
+use log::{debug, info, warn};
+use serde::{de::DeserializeOwned, Serialize};
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, Instant};
+
+use crate::batch::BatchConfig;
+use crate::error::NodeError;
+use crate::ratelimit::TokenBucket;
+use crate::transport::{Reconnect, Transport, TransportError};
+
+/// Weight of the newest sample in the latency and error rate moving averages
+const EWMA_ALPHA: f64 = 0.2;
+
+/// Cooldown after the first throttling response, doubled for each consecutive one
+const BASE_COOLDOWN: Duration = Duration::from_secs(1);
+
+/// Upper bound for the throttling cooldown
+const MAX_COOLDOWN: Duration = Duration::from_secs(60);
+
+/// Penalty added to the score of an endpoint that is cooling down after throttling
+const COOLDOWN_PENALTY: f64 = 1e9;
+
+/// Methods installing a filter that only exists on the endpoint handling the call
+const NEW_FILTER_METHODS: [&str; 3] = ["eth_newFilter", "eth_newBlockFilter", "eth_newPendingTransactionFilter"];
+
+/// Methods taking a filter id as their first parameter
+const FILTER_METHODS: [&str; 3] = ["eth_getFilterChanges", "eth_getFilterLogs", "eth_uninstallFilter"];
+
+/// Rolling health statistics for a single endpoint
+#[derive(Debug, Clone, Default)]
+pub struct EndpointHealth {
+    /// Moving average of request latency in milliseconds
+    pub latency_ms: f64,
+    /// Moving average of the failure ratio (0.0 - 1.0)
+    pub error_rate: f64,
+    pub requests: u64,
+    pub errors: u64,
+    /// Responses classified as throttling (-32046, -32005, 429)
+    pub rate_limited: u64,
+    consecutive_throttles: u32,
+    cooldown_until: Option<Instant>,
+}
+
+impl EndpointHealth {
+    /// Score used for routing, lower is healthier
+    fn score(&self, now: Instant) -> f64 {
+        let mut score = (self.latency_ms + 1.0) * (1.0 + 10.0 * self.error_rate);
+        if self.cooldown_until.is_some_and(|until| until > now) {
+            score += COOLDOWN_PENALTY;
+        }
+        score
+    }
+
+    fn record_latency(&mut self, latency: Duration) {
+        let sample = latency.as_secs_f64() * 1000.0;
+        self.latency_ms = if self.requests == 0 {
+            sample
+        } else {
+            EWMA_ALPHA * sample + (1.0 - EWMA_ALPHA) * self.latency_ms
+        };
+        self.requests += 1;
+    }
+
+    fn record_success(&mut self, latency: Duration) {
+        self.record_latency(latency);
+        self.error_rate *= 1.0 - EWMA_ALPHA;
+        self.consecutive_throttles = 0;
+    }
+
+    fn record_failure(&mut self, latency: Duration, throttled: bool) {
+        self.record_latency(latency);
+        self.error_rate = EWMA_ALPHA + (1.0 - EWMA_ALPHA) * self.error_rate;
+        self.errors += 1;
+
+        if throttled {
+            self.rate_limited += 1;
+            let cooldown = BASE_COOLDOWN
+                .saturating_mul(1 << self.consecutive_throttles.min(6))
+                .min(MAX_COOLDOWN);
+            self.consecutive_throttles += 1;
+            self.cooldown_until = Some(Instant::now() + cooldown);
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Endpoint {
+    url: String,
+    /// Replaced when a socket connection is re-established
+    transport: RwLock<Transport>,
+    health: Mutex<EndpointHealth>,
+}
+
+impl Endpoint {
+    /// Handle to the current connection, cheap to clone
+    fn transport(&self) -> Transport {
+        self.transport.read().unwrap().clone()
+    }
+}
+
+/// Subscription or filter ids handed out by the pool
+///
+/// Endpoints pick their ids independently, so the same id can come from two of them. The pool
+/// hands out its own ids and keeps the endpoint and endpoint-side id behind each.
+#[derive(Debug, Default)]
+struct PinnedIds {
+    last: u64,
+    owners: HashMap<U256, (usize, U256)>,
+}
+
+impl PinnedIds {
+    /// Pool id for an id issued by the endpoint at `index`
+    fn pin(&mut self, index: usize, id: U256) -> U256 {
+        self.last += 1;
+        let pool_id = U256::from(self.last);
+        self.owners.insert(pool_id, (index, id));
+        pool_id
+    }
+}
+
+/// JSON-RPC client that routes every request to the healthiest of several endpoints
+///
+/// Failed or throttled requests are retried on the next endpoint in health order, so a
+/// rate-limited endpoint is skipped without restarting the process. Subscriptions and filters are
+/// pinned to the endpoint that created them, calls using their id are never routed elsewhere.
+#[derive(Debug)]
+pub struct EndpointPool {
+    endpoints: Vec<Endpoint>,
+    /// Active `eth_subscribe` ids
+    subscriptions: Mutex<PinnedIds>,
+    /// Installed filter ids
+    filters: Mutex<PinnedIds>,
+    /// Charged for failover attempts, the first attempt is charged by the caller's rate limiter
+    bucket: Arc<TokenBucket>,
+    /// Settings used when a connection is re-established
+    batch: BatchConfig,
+    /// Held while reconnecting, so feeds failing together open one new connection
+    reconnecting: tokio::sync::Mutex<()>,
+}
+
+impl EndpointPool {
+    /// Connect to every endpoint, skipping the ones that cannot be opened
+    pub async fn connect(urls: &[String], batch: &BatchConfig, bucket: Arc<TokenBucket>) -> Result<Self, NodeError> {
+        let mut endpoints = Vec::new();
+        let mut last_error = None;
+
+        for url in urls {
+            match Transport::connect(url, batch).await {
+                Ok(transport) => endpoints.push(Endpoint {
+                    url: url.clone(),
+                    transport: RwLock::new(transport),
+                    health: Mutex::new(EndpointHealth::default()),
+                }),
+                Err(e) => {
+                    warn!("Skipping endpoint {}: {}", url, e);
+                    last_error = Some(e);
+                }
+            }
+        }
+
+        if endpoints.is_empty() {
+            return Err(last_error.unwrap_or_else(|| NodeError::Other("No endpoints configured".to_string())));
+        }
+
+        Ok(EndpointPool {
+            endpoints,
+            subscriptions: Mutex::new(PinnedIds::default()),
+            filters: Mutex::new(PinnedIds::default()),
+            bucket,
+            batch: batch.clone(),
+            reconnecting: tokio::sync::Mutex::new(()),
+        })
+    }
+
+    /// Whether at least one endpoint can push new data via `eth_subscribe`
+    pub fn supports_pubsub(&self) -> bool {
+        self.endpoints.iter().any(|e| e.transport().supports_pubsub())
+    }
+
+    /// Human readable description of the pool's transports
+    pub fn kind(&self) -> String {
+        if self.endpoints.len() == 1 {
+            return self.endpoints[0].transport().kind().to_string();
+        }
+        let kinds: Vec<&str> = self.endpoints.iter().map(|e| e.transport().kind()).collect();
+        format!("{} endpoints ({})", self.endpoints.len(), kinds.join(", "))
+    }
+
+    /// Snapshot of the health statistics of every endpoint
+    pub fn health(&self) -> Vec<(String, EndpointHealth)> {
+        self.endpoints
+            .iter()
+            .map(|e| (e.url.clone(), e.health.lock().unwrap().clone()))
+            .collect()
+    }
+
+    /// Endpoint indices ordered from healthiest to least healthy
+    fn ranked(&self, pubsub_only: bool) -> Vec<usize> {
+        let now = Instant::now();
+        let mut scored: Vec<(usize, f64)> = self
+            .endpoints
+            .iter()
+            .enumerate()
+            .filter(|(_, e)| !pubsub_only || e.transport().supports_pubsub())
+            .map(|(i, e)| (i, e.health.lock().unwrap().score(now)))
+            .collect();
+        scored.sort_by(|a, b| a.1.total_cmp(&b.1));
+        scored.into_iter().map(|(i, _)| i).collect()
+    }
+
+    /// Send a request to one endpoint, recording the outcome in its health
+    async fn send_to<T, R>(&self, index: usize, method: &str, params: &T) -> Result<R, TransportError>
+    where
+        T: Debug + Serialize + Send + Sync,
+        R: DeserializeOwned + Send,
+    {
+        let endpoint = &self.endpoints[index];
+        let start = Instant::now();
+        let result = JsonRpcClient::request(&endpoint.transport(), method, params).await;
+        let mut health = endpoint.health.lock().unwrap();
+        match &result {
+            Ok(_) => health.record_success(start.elapsed()),
+            Err(e) => {
+                let kind = NodeError::from(e);
+                if kind.is_endpoint_failure() {
+                    health.record_failure(start.elapsed(), kind.is_rate_limited());
+                } else {
+                    // The node answered, the request itself was rejected
+                    health.record_success(start.elapsed());
+                }
+            }
+        }
+        result
+    }
+
+    /// Send a request to the healthiest endpoint, failing over to the next one on endpoint errors
+    async fn dispatch<T, R>(&self, method: &str, params: &T, pubsub_only: bool) -> Result<(usize, R), TransportError>
+    where
+        T: Debug + Serialize + Send + Sync,
+        R: DeserializeOwned + Send,
+    {
+        let mut last_error = TransportError::PubsubUnsupported;
+
+        for (attempt, index) in self.ranked(pubsub_only).into_iter().enumerate() {
+            // Every failover reaches another provider and costs budget of its own
+            if attempt > 0 {
+                self.bucket.acquire(method).await;
+            }
+            match self.send_to(index, method, params).await {
+                Ok(result) => return Ok((index, result)),
+                Err(e) if !NodeError::from(&e).is_endpoint_failure() => return Err(e),
+                Err(e) => {
+                    debug!("{} failed on {}, failing over: {}", method, self.endpoints[index].url, e);
+                    last_error = e;
+                }
+            }
+        }
+
+        Err(last_error)
+    }
+
+    /// Endpoint owning the id passed as first parameter, with the parameters rewritten to its own id
+    ///
+    /// A released id is forgotten by the pool.
+    fn resolve<T: Serialize>(ids: &Mutex<PinnedIds>, params: &T, release: bool) -> Option<(usize, serde_json::Value)> {
+        let mut params = serde_json::to_value(params).ok()?;
+        let id: U256 = params.get(0).cloned().and_then(|id| serde_json::from_value(id).ok())?;
+        let mut ids = ids.lock().unwrap();
+        let (index, endpoint_id) = if release { ids.owners.remove(&id)? } else { *ids.owners.get(&id)? };
+        params[0] = serde_json::to_value(endpoint_id).ok()?;
+        Some((index, params))
+    }
+}
+
+#[async_trait]
+impl JsonRpcClient for EndpointPool {
+    type Error = TransportError;
+
+    async fn request<T, R>(&self, method: &str, params: T) -> Result<R, TransportError>
+    where
+        T: Debug + Serialize + Send + Sync,
+        R: DeserializeOwned + Send,
+    {
+        let pinned = match method {
+            // The stream forgets the subscription when it is dropped, see `PubsubClient::unsubscribe`
+            "eth_unsubscribe" => Some((&self.subscriptions, false)),
+            "eth_uninstallFilter" => Some((&self.filters, true)),
+            method if FILTER_METHODS.contains(&method) => Some((&self.filters, false)),
+            _ => None,
+        };
+        if let Some((ids, release)) = pinned {
+            if let Some((index, params)) = Self::resolve(ids, &params, release) {
+                // Another endpoint does not know the id, a failure lets the caller subscribe or install again
+                return self.send_to(index, method, &params).await;
+            }
+        }
+
+        let owners = match method {
+            "eth_subscribe" => &self.subscriptions,
+            method if NEW_FILTER_METHODS.contains(&method) => &self.filters,
+            method => return self.dispatch(method, &params, false).await.map(|(_, result)| result),
+        };
+
+        // Remember which endpoint owns the subscription or filter so later calls reach it
+        let (index, id) = self.dispatch::<_, U256>(method, &params, method == "eth_subscribe").await?;
+        let id = owners.lock().unwrap().pin(index, id);
+        Ok(serde_json::from_value(serde_json::to_value(id)?)?)
+    }
+}
+
+#[async_trait]
+impl Reconnect for EndpointPool {
+    /// Replace the dead connections of push-capable endpoints, keeping those that cannot be reopened
+    ///
+    /// Subscriptions of the old connections are forgotten, they have to be issued again.
+    async fn reconnect(&self) -> Result<(), NodeError> {
+        let _reconnecting = self.reconnecting.lock().await;
+        let mut last_error = None;
+        for (index, endpoint) in self.endpoints.iter().enumerate() {
+            let transport = endpoint.transport();
+            if !transport.supports_pubsub() {
+                continue;
+            }
+            // Another feed may have reconnected already, a live connection is kept
+            if JsonRpcClient::request::<_, U64>(&transport, "eth_blockNumber", ()).await.is_ok() {
+                continue;
+            }
+            match Transport::connect(&endpoint.url, &self.batch).await {
+                Ok(transport) => {
+                    *endpoint.transport.write().unwrap() = transport;
+                    self.subscriptions.lock().unwrap().owners.retain(|_, (owner, _)| *owner != index);
+                    info!("Reconnected to {}", endpoint.url);
+                }
+                Err(e) => {
+                    warn!("Failed to reconnect to {}: {}", endpoint.url, e);
+                    last_error = Some(e);
+                }
+            }
+        }
+        last_error.map_or(Ok(()), Err)
+    }
+}
+
+impl PubsubClient for EndpointPool {
+    type NotificationStream = <Transport as PubsubClient>::NotificationStream;
+
+    fn subscribe<T: Into<U256>>(&self, id: T) -> Result<Self::NotificationStream, TransportError> {
+        let owner = self.subscriptions.lock().unwrap().owners.get(&id.into()).copied();
+        match owner {
+            Some((index, id)) => self.endpoints[index].transport().subscribe(id),
+            None => Err(TransportError::PubsubUnsupported),
+        }
+    }
+
+    fn unsubscribe<T: Into<U256>>(&self, id: T) -> Result<(), TransportError> {
+        let owner = self.subscriptions.lock().unwrap().owners.remove(&id.into());
+        match owner {
+            Some((index, id)) => self.endpoints[index].transport().unsubscribe(id),
+            None => Err(TransportError::PubsubUnsupported),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pins_equal_ids_of_different_endpoints_apart() {
+        let ids = Mutex::new(PinnedIds::default());
+        let first = ids.lock().unwrap().pin(0, U256::one());
+        let second = ids.lock().unwrap().pin(1, U256::one());
+        assert_ne!(first, second);
+
+        let (index, params) = EndpointPool::resolve(&ids, &[second], false).unwrap();
+        assert_eq!((index, params), (1, serde_json::json!([U256::one()])));
+        // Released ids are forgotten, the other endpoint's subscription is kept
+        assert!(EndpointPool::resolve(&ids, &[second], true).is_some());
+        assert!(EndpointPool::resolve(&ids, &[second], false).is_none());
+        assert_eq!(EndpointPool::resolve(&ids, &[first], false).map(|(index, _)| index), Some(0));
+    }
+}
@@ -0,0 +1,218 @@
+use ethers::prelude::*;
+use log::{debug, error};
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc;
+
+use crate::txpool::PendingTx;
+
+/// Maximum number of transaction hashes whose arrivals are remembered
+const MAX_TRACKED_HASHES: usize = 50_000;
+
+/// Arrival record of a single transaction hash across all endpoints
+#[derive(Debug, Clone)]
+pub struct Sighting {
+    /// Index of the endpoint that delivered the hash first
+    pub first_endpoint: usize,
+    pub first_seen: Instant,
+    /// Delay behind the first arrival at each endpoint, `None` if not (yet) seen there
+    pub deltas: Vec<Option<Duration>>,
+}
+
+/// First-seen statistics for one endpoint
+#[derive(Debug, Clone, Default)]
+pub struct EndpointRaceStats {
+    pub endpoint: String,
+    /// Hashes this endpoint delivered
+    pub seen: u64,
+    /// Hashes this endpoint delivered before every other endpoint
+    pub wins: u64,
+    /// Sum of the delays behind the winner for hashes this endpoint lost
+    pub total_delay: Duration,
+}
+
+impl EndpointRaceStats {
+    /// Share of all distinct hashes that this endpoint delivered first
+    pub fn win_rate(&self, total: u64) -> f64 {
+        if total == 0 {
+            0.0
+        } else {
+            self.wins as f64 / total as f64
+        }
+    }
+
+    /// Average delay behind the winner for hashes this endpoint lost
+    pub fn average_delay(&self) -> Duration {
+        let losses = self.seen - self.wins;
+        if losses == 0 {
+            Duration::ZERO
+        } else {
+            self.total_delay / losses as u32
+        }
+    }
+}
+
+/// Records which endpoint saw each pending transaction first and how far behind the others were
+#[derive(Debug)]
+pub struct RaceTracker {
+    stats: Vec<EndpointRaceStats>,
+    sightings: HashMap<H256, Sighting>,
+    /// Insertion order of `sightings`, used to evict the oldest hashes
+    order: VecDeque<H256>,
+    /// Distinct hashes seen across all endpoints
+    total: u64,
+}
+
+impl RaceTracker {
+    pub fn new(endpoints: &[String]) -> Self {
+        RaceTracker {
+            stats: endpoints
+                .iter()
+                .map(|endpoint| EndpointRaceStats {
+                    endpoint: endpoint.clone(),
+                    ..Default::default()
+                })
+                .collect(),
+            sightings: HashMap::new(),
+            order: VecDeque::new(),
+            total: 0,
+        }
+    }
+
+    /// Record the arrival of a hash at an endpoint, returning `true` if no endpoint had seen it before
+    pub fn record(&mut self, endpoint: usize, hash: H256, at: Instant) -> bool {
+        if let Some(sighting) = self.sightings.get_mut(&hash) {
+            if sighting.first_endpoint == endpoint || sighting.deltas[endpoint].is_some() {
+                // Repeated delivery from the same endpoint
+                return false;
+            }
+            if at < sighting.first_seen {
+                // Hydration completes out of order, so an earlier arrival can be reported late
+                let shift = sighting.first_seen - at;
+                for (index, delta) in sighting.deltas.iter_mut().enumerate() {
+                    if let Some(delta) = delta {
+                        *delta += shift;
+                        self.stats[index].total_delay += shift;
+                    }
+                }
+                self.stats[sighting.first_endpoint].wins -= 1;
+                sighting.first_endpoint = endpoint;
+                sighting.first_seen = at;
+                sighting.deltas[endpoint] = Some(Duration::ZERO);
+                self.stats[endpoint].seen += 1;
+                self.stats[endpoint].wins += 1;
+                return false;
+            }
+            let delta = at.saturating_duration_since(sighting.first_seen);
+            sighting.deltas[endpoint] = Some(delta);
+            self.stats[endpoint].seen += 1;
+            self.stats[endpoint].total_delay += delta;
+            return false;
+        }
+
+        let mut deltas = vec![None; self.stats.len()];
+        deltas[endpoint] = Some(Duration::ZERO);
+        self.sightings.insert(hash, Sighting {
+            first_endpoint: endpoint,
+            first_seen: at,
+            deltas,
+        });
+        self.order.push_back(hash);
+        if self.order.len() > MAX_TRACKED_HASHES {
+            if let Some(oldest) = self.order.pop_front() {
+                self.sightings.remove(&oldest);
+            }
+        }
+
+        self.total += 1;
+        self.stats[endpoint].seen += 1;
+        self.stats[endpoint].wins += 1;
+        true
+    }
+
+    /// Median delay behind the first arrival over the tracked hashes an endpoint delivered
+    pub fn median_delta(&self, endpoint: usize) -> Option<Duration> {
+        let mut deltas: Vec<Duration> = self.sightings.values().filter_map(|sighting| sighting.deltas[endpoint]).collect();
+        if deltas.is_empty() {
+            return None;
+        }
+        deltas.sort_unstable();
+        Some(deltas[deltas.len() / 2])
+    }
+
+    /// Per-endpoint first-seen statistics
+    pub fn summary(&self) -> Vec<EndpointRaceStats> {
+        self.stats.clone()
+    }
+
+    /// Number of distinct hashes seen across all endpoints
+    pub fn total(&self) -> u64 {
+        self.total
+    }
+}
+
+/// Merge the pending transaction streams of several endpoints into one deduplicated feed
+///
+/// Each receiver carries the output of `subscribe_to_pending_transactions` (or its push
+/// equivalent) for one endpoint, in the same order as the endpoints given to the tracker.
+/// Arrivals are timed by the `seen_at` of each transaction, so hydration latency does not count
+/// against an endpoint. Only the first arrival of each hash is forwarded to `tx_sender`.
+pub async fn merge_pending_streams(
+    receivers: Vec<mpsc::Receiver<PendingTx>>,
+    tx_sender: mpsc::Sender<PendingTx>,
+    tracker: Arc<Mutex<RaceTracker>>,
+) {
+    let (merged_sender, mut merged_receiver) = mpsc::channel(1000);
+
+    for (endpoint, mut receiver) in receivers.into_iter().enumerate() {
+        let merged_sender = merged_sender.clone();
+        tokio::spawn(async move {
+            while let Some(pending) = receiver.recv().await {
+                if merged_sender.send((endpoint, pending)).await.is_err() {
+                    break;
+                }
+            }
+        });
+    }
+    drop(merged_sender);
+
+    while let Some((endpoint, pending)) = merged_receiver.recv().await {
+        let hash = pending.tx.hash;
+        let first = tracker.lock().unwrap().record(endpoint, hash, pending.seen_at);
+        if first {
+            if let Err(e) = tx_sender.send(pending).await {
+                error!("Failed to send transaction to channel: {}", e);
+            }
+        } else {
+            debug!("Transaction {:?} already seen, arrived late from endpoint #{}", hash, endpoint);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn credits_the_earliest_arrival_and_tracks_deltas() {
+        let mut tracker = RaceTracker::new(&["a".to_string(), "b".to_string()]);
+        let start = Instant::now();
+        let ms = Duration::from_millis;
+
+        assert!(tracker.record(0, H256::repeat_byte(1), start));
+        assert!(!tracker.record(1, H256::repeat_byte(1), start + ms(30)));
+        assert!(tracker.record(1, H256::repeat_byte(2), start + ms(50)));
+        // Reported late but seen earlier at endpoint 0, which takes the win over
+        assert!(!tracker.record(0, H256::repeat_byte(2), start + ms(40)));
+        assert!(tracker.record(0, H256::repeat_byte(3), start + ms(60)));
+        assert!(!tracker.record(0, H256::repeat_byte(3), start + ms(70)));
+
+        let summary = tracker.summary();
+        assert_eq!(tracker.total(), 3);
+        assert_eq!((summary[0].wins, summary[0].seen), (3, 3));
+        assert_eq!((summary[1].wins, summary[1].seen), (0, 2));
+        assert_eq!(tracker.median_delta(0), Some(Duration::ZERO));
+        assert_eq!(tracker.median_delta(1), Some(ms(30)));
+    }
+}
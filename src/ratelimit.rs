@@ -0,0 +1,272 @@
+use async_trait::async_trait;
+use ethers::prelude::*;
+use ethers::providers::{JsonRpcClient, PubsubClient};
+use log::warn;
+use serde::{de::DeserializeOwned, Serialize};
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::time::sleep;
+
+use crate::error::NodeError;
+use crate::transport::{Reconnect, TransportError};
+
+/// Factor applied to the effective rate when the endpoint throttles us
+const DECREASE_FACTOR: f64 = 0.5;
+
+/// Queries per second added to the effective rate for every successful request
+const INCREASE_STEP: f64 = 0.1;
+
+/// Lowest effective rate adaptive mode will back off to
+const MIN_RATE: f64 = 1.0;
+
+/// Minimum time between two rate decreases, so one burst of throttled responses counts once
+const DECREASE_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Token bucket settings shared by every RPC call of a provider
+#[derive(Debug, Clone)]
+pub struct RateLimitConfig {
+    /// Sustained budget in weight units per second, 0 disables limiting
+    pub rate: u32,
+    /// Maximum budget that can be spent at once after an idle period
+    pub burst: u32,
+    /// Per-method weights (e.g. provider compute units), methods not listed cost 1
+    pub weights: HashMap<String, u32>,
+    /// Back off on throttling and creep back up to `rate` on success (AIMD)
+    pub adaptive: bool,
+}
+
+#[derive(Debug)]
+struct BucketState {
+    /// Available budget, negative while callers are waiting for reserved budget
+    tokens: f64,
+    refilled_at: Instant,
+    /// Effective rate, equal to the ceiling unless adaptive mode backed off
+    rate: f64,
+    decreased_at: Option<Instant>,
+    throttle_events: u64,
+}
+
+/// Token bucket limiting the weighted request rate
+#[derive(Debug)]
+pub struct TokenBucket {
+    /// Configured rate, the upper bound for the effective rate
+    ceiling: f64,
+    burst: f64,
+    adaptive: bool,
+    weights: HashMap<String, f64>,
+    state: Mutex<BucketState>,
+}
+
+impl TokenBucket {
+    pub fn new(config: &RateLimitConfig) -> Self {
+        let burst = config.burst.max(1) as f64;
+        let ceiling = config.rate as f64;
+        TokenBucket {
+            ceiling,
+            burst,
+            adaptive: config.adaptive && ceiling > 0.0,
+            weights: config.weights.iter().map(|(method, weight)| (method.clone(), *weight as f64)).collect(),
+            state: Mutex::new(BucketState {
+                tokens: burst,
+                refilled_at: Instant::now(),
+                rate: ceiling,
+                decreased_at: None,
+                throttle_events: 0,
+            }),
+        }
+    }
+
+    /// Budget consumed by a single call of the method
+    pub fn weight(&self, method: &str) -> f64 {
+        self.weights.get(method).copied().unwrap_or(1.0)
+    }
+
+    /// Wait until the budget for one call of the method is available
+    ///
+    /// Budget is reserved up front, so concurrent callers are served in arrival order.
+    pub async fn acquire(&self, method: &str) {
+        if self.ceiling <= 0.0 {
+            return;
+        }
+
+        let wait = {
+            let mut state = self.state.lock().unwrap();
+            let now = Instant::now();
+            let refill = now.duration_since(state.refilled_at).as_secs_f64() * state.rate;
+            state.tokens = (state.tokens + refill).min(self.burst);
+            state.refilled_at = now;
+
+            state.tokens -= self.weight(method);
+            if state.tokens >= 0.0 {
+                return;
+            }
+            Duration::from_secs_f64(-state.tokens / state.rate)
+        };
+
+        sleep(wait).await;
+    }
+
+    /// Whether the effective rate adapts to throttling
+    pub fn is_adaptive(&self) -> bool {
+        self.adaptive
+    }
+
+    /// Current effective rate in weight units per second
+    pub fn current_rate(&self) -> f64 {
+        self.state.lock().unwrap().rate
+    }
+
+    /// Number of throttling or timeout responses seen in adaptive mode
+    pub fn throttle_events(&self) -> u64 {
+        self.state.lock().unwrap().throttle_events
+    }
+
+    /// Additive increase after a successful request
+    fn on_success(&self) {
+        if !self.adaptive {
+            return;
+        }
+        let mut state = self.state.lock().unwrap();
+        state.rate = (state.rate + INCREASE_STEP).min(self.ceiling);
+    }
+
+    /// Multiplicative decrease after a throttling or timeout response
+    fn on_throttle(&self) {
+        if !self.adaptive {
+            return;
+        }
+        let mut state = self.state.lock().unwrap();
+        state.throttle_events += 1;
+        let now = Instant::now();
+        if state.decreased_at.is_some_and(|at| now.duration_since(at) < DECREASE_INTERVAL) {
+            return;
+        }
+        state.rate = (state.rate * DECREASE_FACTOR).max(MIN_RATE.min(self.ceiling));
+        state.decreased_at = Some(now);
+        warn!("Endpoint is throttling, reducing query rate to {:.1}/s", state.rate);
+    }
+}
+
+/// JSON-RPC client that charges every request against a shared token bucket
+#[derive(Debug)]
+pub struct RateLimited<P> {
+    inner: P,
+    bucket: Arc<TokenBucket>,
+}
+
+impl<P> RateLimited<P> {
+    pub fn new(inner: P, bucket: Arc<TokenBucket>) -> Self {
+        RateLimited { inner, bucket }
+    }
+
+    /// The wrapped client
+    pub fn inner(&self) -> &P {
+        &self.inner
+    }
+
+    /// The token bucket shared by all requests
+    pub fn bucket(&self) -> &Arc<TokenBucket> {
+        &self.bucket
+    }
+}
+
+#[async_trait]
+impl<P: JsonRpcClient<Error = TransportError>> JsonRpcClient for RateLimited<P> {
+    type Error = P::Error;
+
+    async fn request<T, R>(&self, method: &str, params: T) -> Result<R, P::Error>
+    where
+        T: Debug + Serialize + Send + Sync,
+        R: DeserializeOwned + Send,
+    {
+        self.bucket.acquire(method).await;
+        let result = self.inner.request(method, params).await;
+        match &result {
+            Ok(_) => self.bucket.on_success(),
+            Err(e) if NodeError::from(e).is_throttling() => self.bucket.on_throttle(),
+            Err(_) => {}
+        }
+        result
+    }
+}
+
+#[async_trait]
+impl<P: Reconnect + Send + Sync> Reconnect for RateLimited<P> {
+    // Opening a connection is not a JSON-RPC call, so it is not charged
+    async fn reconnect(&self) -> Result<(), NodeError> {
+        self.inner.reconnect().await
+    }
+}
+
+impl<P: PubsubClient + JsonRpcClient<Error = TransportError>> PubsubClient for RateLimited<P> {
+    type NotificationStream = P::NotificationStream;
+
+    // Pushed notifications are free, only the `eth_subscribe` request itself is charged
+    fn subscribe<T: Into<U256>>(&self, id: T) -> Result<Self::NotificationStream, P::Error> {
+        self.inner.subscribe(id)
+    }
+
+    fn unsubscribe<T: Into<U256>>(&self, id: T) -> Result<(), P::Error> {
+        self.inner.unsubscribe(id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn adaptive_bucket(rate: u32) -> TokenBucket {
+        TokenBucket::new(&RateLimitConfig { rate, burst: rate, weights: HashMap::new(), adaptive: true })
+    }
+
+    /// Let the next throttling response decrease the rate again
+    fn end_decrease_interval(bucket: &TokenBucket) {
+        bucket.state.lock().unwrap().decreased_at = None;
+    }
+
+    #[test]
+    fn halves_on_throttling_once_per_interval() {
+        let bucket = adaptive_bucket(8);
+        bucket.on_throttle();
+        assert_eq!(bucket.current_rate(), 4.0);
+        // The same burst of throttled responses counts once
+        bucket.on_throttle();
+        assert_eq!(bucket.current_rate(), 4.0);
+        assert_eq!(bucket.throttle_events(), 2);
+
+        end_decrease_interval(&bucket);
+        bucket.on_throttle();
+        assert_eq!(bucket.current_rate(), 2.0);
+    }
+
+    #[test]
+    fn increases_additively_up_to_the_ceiling() {
+        let bucket = adaptive_bucket(2);
+        bucket.on_throttle();
+        assert_eq!(bucket.current_rate(), 1.0);
+        for _ in 0..5 {
+            bucket.on_success();
+        }
+        assert!((bucket.current_rate() - 1.5).abs() < 1e-9);
+        for _ in 0..100 {
+            bucket.on_success();
+        }
+        assert_eq!(bucket.current_rate(), 2.0);
+    }
+
+    #[test]
+    fn never_backs_off_below_the_floor() {
+        let bucket = adaptive_bucket(3);
+        for _ in 0..10 {
+            end_decrease_interval(&bucket);
+            bucket.on_throttle();
+        }
+        assert_eq!(bucket.current_rate(), MIN_RATE);
+
+        let fixed = TokenBucket::new(&RateLimitConfig { rate: 3, burst: 3, weights: HashMap::new(), adaptive: false });
+        fixed.on_throttle();
+        assert_eq!((fixed.current_rate(), fixed.throttle_events()), (3.0, 0));
+    }
+}
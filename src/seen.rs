@@ -0,0 +1,121 @@
+use ethers::prelude::*;
+use std::collections::HashSet;
+use std::mem;
+use std::time::{Duration, Instant};
+
+/// Bounded set of recently seen transaction hashes with O(1) lookups
+///
+/// Hashes are kept in two generations. The current generation is rotated into the previous one
+/// every `ttl / 2` or when it reaches half the capacity, and the previous generation is dropped on
+/// the next rotation. A hash seen again while in the previous generation is moved back into the
+/// current one, so transactions that stay in the mempool are not re-emitted, while hashes that
+/// have not been seen for `ttl` are forgotten.
+#[derive(Debug)]
+pub struct SeenSet {
+    current: HashSet<H256>,
+    previous: HashSet<H256>,
+    rotated_at: Instant,
+    ttl: Duration,
+    capacity: usize,
+    inserted: u64,
+    duplicates: u64,
+}
+
+impl SeenSet {
+    pub fn new(capacity: usize, ttl: Duration) -> Self {
+        SeenSet {
+            current: HashSet::new(),
+            previous: HashSet::new(),
+            rotated_at: Instant::now(),
+            ttl,
+            capacity: capacity.max(2),
+            inserted: 0,
+            duplicates: 0,
+        }
+    }
+
+    /// Mark a hash as seen, returning `true` if it was not seen before
+    pub fn insert(&mut self, hash: H256) -> bool {
+        self.rotate_if_due();
+
+        if self.current.contains(&hash) {
+            self.duplicates += 1;
+            return false;
+        }
+        if self.previous.remove(&hash) {
+            // Still around, refresh it into the current generation
+            self.current.insert(hash);
+            self.duplicates += 1;
+            return false;
+        }
+
+        self.current.insert(hash);
+        self.inserted += 1;
+        true
+    }
+
+    /// Check whether a hash was seen recently without updating any counters
+    pub fn contains(&self, hash: &H256) -> bool {
+        self.current.contains(hash) || self.previous.contains(hash)
+    }
+
+    /// Number of distinct hashes inserted
+    pub fn inserted(&self) -> u64 {
+        self.inserted
+    }
+
+    /// Number of repeated deliveries that were suppressed
+    pub fn duplicates(&self) -> u64 {
+        self.duplicates
+    }
+
+    fn rotate_if_due(&mut self) {
+        if self.rotated_at.elapsed() >= self.ttl / 2 || self.current.len() >= self.capacity / 2 {
+            self.previous = mem::take(&mut self.current);
+            self.rotated_at = Instant::now();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hash(n: u64) -> H256 {
+        H256::from_low_u64_be(n)
+    }
+
+    #[test]
+    fn reports_duplicates() {
+        let mut seen = SeenSet::new(100, Duration::from_secs(60));
+        assert!(seen.insert(hash(1)));
+        assert!(!seen.insert(hash(1)));
+        assert!(seen.insert(hash(2)));
+        assert_eq!((seen.inserted(), seen.duplicates()), (2, 1));
+    }
+
+    #[test]
+    fn keeps_hashes_for_exactly_one_rotation() {
+        // Rotates whenever the current generation holds two hashes
+        let mut seen = SeenSet::new(4, Duration::from_secs(60));
+        seen.insert(hash(1));
+        seen.insert(hash(2));
+        seen.insert(hash(3));
+        assert!(seen.contains(&hash(1)));
+
+        seen.insert(hash(4));
+        seen.insert(hash(5));
+        assert!(!seen.contains(&hash(1)));
+        assert!(seen.contains(&hash(3)));
+        assert!(seen.insert(hash(1)));
+    }
+
+    #[test]
+    fn stays_within_capacity() {
+        let mut seen = SeenSet::new(10, Duration::from_secs(60));
+        for n in 0..1000 {
+            assert!(seen.insert(hash(n)));
+            assert!(seen.current.len() + seen.previous.len() <= 10);
+        }
+    }
+}
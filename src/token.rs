@@ -0,0 +1,148 @@
+use ethers::abi::{decode, ParamType, Token};
+use ethers::prelude::*;
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use crate::error::NodeError;
+
+/// ERC-20 calls carrying an amount, with the index of the amount among their arguments
+const AMOUNT_CALLS: [([u8; 4], usize); 3] = [
+    ([0xa9, 0x05, 0x9c, 0xbb], 1), // transfer(address,uint256)
+    ([0x09, 0x5e, 0xa7, 0xb3], 1), // approve(address,uint256)
+    ([0x23, 0xb8, 0x72, 0xdd], 2), // transferFrom(address,address,uint256)
+];
+
+/// Selector of `decimals()`
+const DECIMALS_SELECTOR: [u8; 4] = [0x31, 0x3c, 0xe5, 0x67];
+
+/// Selector of `symbol()`
+const SYMBOL_SELECTOR: [u8; 4] = [0x95, 0xd8, 0x9b, 0x41];
+
+/// Shown in place of a symbol the token does not report
+const UNKNOWN_SYMBOL: &str = "tokens";
+
+/// Raw amount moved or approved by an ERC-20 `transfer`, `approve` or `transferFrom` call
+pub fn token_amount(input: &Bytes) -> Option<U256> {
+    let (selector, args) = input.0.split_first_chunk::<4>()?;
+    let (_, index) = AMOUNT_CALLS.iter().find(|(call, _)| call == selector)?;
+    let word = args.get(index * 32..(index + 1) * 32)?;
+    Some(U256::from_big_endian(word))
+}
+
+/// Metadata needed to show raw token amounts
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TokenInfo {
+    pub decimals: u8,
+    pub symbol: String,
+}
+
+/// Decode the return data of `decimals()`, `None` unless it is a `uint8`
+fn decode_decimals(data: &[u8]) -> Option<u8> {
+    let word = data.get(..32)?;
+    let decimals = U256::from_big_endian(word);
+    (decimals <= U256::from(u8::MAX)).then(|| decimals.as_u32() as u8)
+}
+
+/// Decode the return data of `symbol()`, a `string` or, for some early tokens, a `bytes32`
+fn decode_symbol(data: &[u8]) -> Option<String> {
+    let symbol = match decode(&[ParamType::String], data).ok().and_then(|tokens| tokens.into_iter().next()) {
+        Some(Token::String(symbol)) => symbol,
+        _ if data.len() == 32 => String::from_utf8(data.to_vec()).ok()?,
+        _ => return None,
+    };
+    let symbol: String = symbol.chars().filter(|c| !c.is_control()).collect();
+    let symbol = symbol.trim();
+    (!symbol.is_empty()).then(|| symbol.to_string())
+}
+
+async fn call<M: Middleware>(provider: &M, token: Address, selector: [u8; 4]) -> Result<Bytes, NodeError> {
+    let request = TransactionRequest::new().to(token).data(selector.to_vec());
+    provider.call(&request.into(), None).await.map_err(|e| NodeError::from_middleware(&e))
+}
+
+/// Metadata of the tokens seen so far, each token is queried once
+#[derive(Debug, Default)]
+pub struct TokenCache {
+    tokens: Mutex<HashMap<Address, Option<TokenInfo>>>,
+}
+
+impl TokenCache {
+    /// Metadata of `token` from its `decimals()` and `symbol()`, `None` if it reports no decimals
+    ///
+    /// Contracts that are not tokens are remembered as such, failures of the endpoint are retried
+    /// on the next lookup.
+    pub async fn get<M: Middleware>(&self, provider: &M, token: Address) -> Option<TokenInfo> {
+        if let Some(info) = self.tokens.lock().unwrap().get(&token) {
+            return info.clone();
+        }
+
+        let (decimals, symbol) = futures::join!(
+            call(provider, token, DECIMALS_SELECTOR),
+            call(provider, token, SYMBOL_SELECTOR),
+        );
+        if [&decimals, &symbol].iter().any(|result| result.as_ref().is_err_and(NodeError::is_endpoint_failure)) {
+            return None;
+        }
+        let info = decimals.ok().and_then(|data| decode_decimals(&data)).map(|decimals| TokenInfo {
+            decimals,
+            symbol: symbol.ok().and_then(|data| decode_symbol(&data)).unwrap_or_else(|| UNKNOWN_SYMBOL.to_string()),
+        });
+        self.tokens.lock().unwrap().insert(token, info.clone());
+        info
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ethers::abi::encode;
+    use ethers::providers::MockProvider;
+
+    fn calldata(selector: [u8; 4], args: &[Token]) -> Bytes {
+        let mut data = selector.to_vec();
+        data.extend(encode(args));
+        data.into()
+    }
+
+    #[test]
+    fn decodes_amounts_of_transfers_and_approvals() {
+        let recipient = Token::Address(Address::repeat_byte(1));
+        let amount = Token::Uint(U256::from(1_500_250_000u64));
+
+        let transfer = calldata(AMOUNT_CALLS[0].0, &[recipient.clone(), amount.clone()]);
+        let approve = calldata(AMOUNT_CALLS[1].0, &[recipient.clone(), amount.clone()]);
+        let transfer_from = calldata(AMOUNT_CALLS[2].0, &[recipient.clone(), recipient.clone(), amount]);
+
+        for input in [transfer, approve, transfer_from] {
+            assert_eq!(token_amount(&input), Some(U256::from(1_500_250_000u64)));
+        }
+        assert_eq!(token_amount(&calldata(DECIMALS_SELECTOR, &[])), None);
+        // Truncated calldata
+        assert_eq!(token_amount(&calldata(AMOUNT_CALLS[0].0, &[recipient])), None);
+    }
+
+    #[test]
+    fn decodes_string_and_bytes32_symbols() {
+        assert_eq!(decode_symbol(&encode(&[Token::String("USDC".to_string())])).as_deref(), Some("USDC"));
+        let mut bytes32 = [0u8; 32];
+        bytes32[..3].copy_from_slice(b"MKR");
+        assert_eq!(decode_symbol(&bytes32).as_deref(), Some("MKR"));
+        assert_eq!(decode_symbol(&[]), None);
+        assert_eq!(decode_decimals(&encode(&[Token::Uint(U256::from(6))])), Some(6));
+        assert_eq!(decode_decimals(&encode(&[Token::Uint(U256::from(256))])), None);
+    }
+
+    #[tokio::test]
+    async fn queries_each_token_once() {
+        let mock = MockProvider::new();
+        // Responses are served last in, first out: decimals() is requested first
+        mock.push::<Bytes, _>(Bytes::from(encode(&[Token::String("USDC".to_string())]))).unwrap();
+        mock.push::<Bytes, _>(Bytes::from(encode(&[Token::Uint(U256::from(6))]))).unwrap();
+        let provider = Provider::new(mock);
+        let tokens = TokenCache::default();
+        let usdc = TokenInfo { decimals: 6, symbol: "USDC".to_string() };
+
+        assert_eq!(tokens.get(&provider, Address::repeat_byte(0xa0)).await, Some(usdc.clone()));
+        assert_eq!(tokens.get(&provider, Address::repeat_byte(0xa0)).await, Some(usdc));
+    }
+}
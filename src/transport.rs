@@ -0,0 +1,173 @@
+use async_trait::async_trait;
+use ethers::prelude::*;
+use ethers::providers::{HttpClientError, IpcError, JsonRpcClient, JsonRpcError, PubsubClient, RpcError, WsClientError};
+use futures::channel::mpsc;
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::value::RawValue;
+use std::fmt::Debug;
+use std::time::Duration;
+use thiserror::Error;
+use url::Url;
+
+use crate::batch::{BatchConfig, BatchHttp};
+use crate::error::NodeError;
+
+/// Number of times the WebSocket client reconnects (and replays active subscriptions) before giving up
+const WS_RECONNECTS: usize = 10;
+
+/// Timeout for establishing a socket connection
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Timeout for the response to a single request, a hung endpoint is failed over like a throttling one
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Client whose socket connections can be replaced once they are beyond recovery
+///
+/// The WebSocket client gives up after a failed or its last reconnect attempt and the IPC client
+/// after the socket closed, from then on every request fails until a new connection is opened.
+#[async_trait]
+pub trait Reconnect {
+    /// Open fresh connections to the endpoints that support push subscriptions
+    async fn reconnect(&self) -> Result<(), NodeError>;
+}
+
+/// JSON-RPC transport selected from the scheme of the endpoint URL
+#[derive(Debug, Clone)]
+pub enum Transport {
+    /// Request/response over `http://` or `https://`, calls are coalesced into batch requests if enabled
+    Http(BatchHttp),
+    /// Persistent `ws://` or `wss://` connection with `eth_subscribe` support
+    Ws(Ws),
+    /// Unix domain socket (or Windows named pipe) of a co-located node, with `eth_subscribe` support
+    Ipc(Ipc),
+}
+
+/// Errors raised by any of the underlying transports
+#[derive(Debug, Error)]
+pub enum TransportError {
+    #[error(transparent)]
+    Http(#[from] HttpClientError),
+    #[error(transparent)]
+    Ws(#[from] WsClientError),
+    #[error(transparent)]
+    Ipc(#[from] IpcError),
+    #[error("Failed to decode response: {0}")]
+    Decode(#[from] serde_json::Error),
+    #[error("Push subscriptions are not supported over HTTP")]
+    PubsubUnsupported,
+    #[error("No response to {0} within {timeout:?}", timeout = REQUEST_TIMEOUT)]
+    Timeout(String),
+}
+
+impl RpcError for TransportError {
+    fn as_error_response(&self) -> Option<&JsonRpcError> {
+        match self {
+            TransportError::Http(e) => e.as_error_response(),
+            TransportError::Ws(e) => e.as_error_response(),
+            TransportError::Ipc(e) => e.as_error_response(),
+            TransportError::Decode(_) | TransportError::PubsubUnsupported | TransportError::Timeout(_) => None,
+        }
+    }
+
+    fn as_serde_error(&self) -> Option<&serde_json::Error> {
+        match self {
+            TransportError::Http(e) => e.as_serde_error(),
+            TransportError::Ws(e) => e.as_serde_error(),
+            TransportError::Ipc(e) => e.as_serde_error(),
+            TransportError::Decode(e) => Some(e),
+            TransportError::PubsubUnsupported | TransportError::Timeout(_) => None,
+        }
+    }
+}
+
+impl From<TransportError> for ProviderError {
+    fn from(e: TransportError) -> Self {
+        ProviderError::JsonRpcClientError(Box::new(e))
+    }
+}
+
+impl Transport {
+    /// Open a transport for the endpoint, choosing HTTP or WebSocket from the URL scheme
+    ///
+    /// Endpoints without a scheme (e.g. `/var/lib/geth/geth.ipc`) are treated as IPC socket paths.
+    /// HTTP calls are batched when `batch` is enabled, socket transports are never batched.
+    pub async fn connect(endpoint: &str, batch: &BatchConfig) -> Result<Self, NodeError> {
+        if !endpoint.contains("://") {
+            let ipc = tokio::time::timeout(CONNECT_TIMEOUT, Ipc::connect(endpoint))
+                .await
+                .map_err(|_| NodeError::Timeout("IPC socket did not accept the connection".to_string()))?
+                .map_err(TransportError::from)?;
+            return Ok(Transport::Ipc(ipc));
+        }
+
+        let url = Url::parse(endpoint)?;
+        match url.scheme() {
+            "http" | "https" => Ok(Transport::Http(BatchHttp::new(url, batch.clone()))),
+            "ws" | "wss" => {
+                let ws = tokio::time::timeout(CONNECT_TIMEOUT, Ws::connect_with_reconnects(endpoint, WS_RECONNECTS))
+                    .await
+                    .map_err(|_| NodeError::Timeout("WebSocket handshake did not complete".to_string()))?
+                    .map_err(TransportError::from)?;
+                Ok(Transport::Ws(ws))
+            }
+            scheme => Err(NodeError::InvalidUrl(format!("unsupported scheme '{}' (expected http, https, ws or wss)", scheme))),
+        }
+    }
+
+    /// Whether the node can push new data via `eth_subscribe`
+    pub fn supports_pubsub(&self) -> bool {
+        !matches!(self, Transport::Http(_))
+    }
+
+    /// Human readable transport name
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Transport::Http(client) if client.batches_supported() => "HTTP (batched)",
+            Transport::Http(_) => "HTTP",
+            Transport::Ws(_) => "WebSocket",
+            Transport::Ipc(_) => "IPC",
+        }
+    }
+}
+
+#[async_trait]
+impl JsonRpcClient for Transport {
+    type Error = TransportError;
+
+    async fn request<T, R>(&self, method: &str, params: T) -> Result<R, TransportError>
+    where
+        T: Debug + Serialize + Send + Sync,
+        R: DeserializeOwned + Send,
+    {
+        let request = async {
+            match self {
+                Transport::Http(client) => Ok(JsonRpcClient::request(client, method, params).await?),
+                Transport::Ws(client) => Ok(JsonRpcClient::request(client, method, params).await?),
+                Transport::Ipc(client) => Ok(JsonRpcClient::request(client, method, params).await?),
+            }
+        };
+        tokio::time::timeout(REQUEST_TIMEOUT, request)
+            .await
+            .map_err(|_| TransportError::Timeout(method.to_string()))?
+    }
+}
+
+impl PubsubClient for Transport {
+    type NotificationStream = mpsc::UnboundedReceiver<Box<RawValue>>;
+
+    fn subscribe<T: Into<U256>>(&self, id: T) -> Result<Self::NotificationStream, TransportError> {
+        match self {
+            Transport::Http(_) => Err(TransportError::PubsubUnsupported),
+            Transport::Ws(client) => Ok(client.subscribe(id)?),
+            Transport::Ipc(client) => Ok(client.subscribe(id)?),
+        }
+    }
+
+    fn unsubscribe<T: Into<U256>>(&self, id: T) -> Result<(), TransportError> {
+        match self {
+            Transport::Http(_) => Err(TransportError::PubsubUnsupported),
+            Transport::Ws(client) => Ok(client.unsubscribe(id)?),
+            Transport::Ipc(client) => Ok(client.unsubscribe(id)?),
+        }
+    }
+}
@@ -0,0 +1,191 @@
+use ethers::prelude::*;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fmt;
+use std::time::{Duration, Instant};
+
+/// Which pool a transaction was observed in
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PoolStatus {
+    /// Executable, waiting in the pending pool for inclusion
+    Pending,
+    /// Not executable yet, waiting in the queued pool
+    Queued(QueuedReason),
+    /// Moved from the queued to the pending pool once its nonce gap closed
+    Promoted { queued_for: Duration },
+}
+
+/// Why a queued transaction cannot be executed yet
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum QueuedReason {
+    /// The sender's transaction with this nonce has not been seen
+    MissingNonce(U256),
+    /// The sender has no pending transactions, so one or more nonces below this one are missing
+    GapBelow(U256),
+    /// No nonce gap, e.g. the sender cannot pay for the transaction
+    NotExecutable,
+}
+
+impl fmt::Display for QueuedReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            QueuedReason::MissingNonce(nonce) => write!(f, "nonce gap, waiting for nonce {}", nonce),
+            QueuedReason::GapBelow(nonce) => write!(f, "nonce gap, nothing pending from sender below nonce {}", nonce),
+            QueuedReason::NotExecutable => write!(f, "not executable (e.g. insufficient balance)"),
+        }
+    }
+}
+
+/// Transaction together with the time it was delivered by the node and the pool it was seen in
+#[derive(Debug, Clone)]
+pub struct PendingTx {
+    pub tx: Transaction,
+    /// Arrival time of the hash, lookups complete out of order so this is the ordering key
+    pub seen_at: Instant,
+    pub status: PoolStatus,
+}
+
+impl PendingTx {
+    /// Wrap a pending transaction that arrived as a full object
+    pub fn new(tx: Transaction) -> Self {
+        PendingTx::with_status(tx, PoolStatus::Pending)
+    }
+
+    pub fn with_status(tx: Transaction, status: PoolStatus) -> Self {
+        PendingTx {
+            tx,
+            seen_at: Instant::now(),
+            status,
+        }
+    }
+}
+
+/// Transactions that entered or left the queued pool between two `txpool_content` snapshots
+#[derive(Debug, Default)]
+pub struct QueuedUpdate {
+    /// Newly queued transactions with the reason they are stuck
+    pub entered: Vec<(Transaction, QueuedReason)>,
+    /// Time spent queued by transactions that moved to the pending pool
+    pub promoted: HashMap<H256, Duration>,
+}
+
+/// Tracks the queued (nonce-gapped) pool across `txpool_content` snapshots
+#[derive(Debug, Default)]
+pub struct QueuedPool {
+    /// First time each currently queued transaction was seen
+    since: HashMap<H256, Instant>,
+}
+
+impl QueuedPool {
+    /// Reconcile with a new snapshot of the node's transaction pool
+    pub fn update(&mut self, content: &TxpoolContent) -> QueuedUpdate {
+        let now = Instant::now();
+        let mut update = QueuedUpdate::default();
+        let mut since = HashMap::new();
+
+        for (sender, queued) in &content.queued {
+            let pending = content.pending.get(sender);
+            for tx in queued.values() {
+                match self.since.remove(&tx.hash) {
+                    Some(at) => {
+                        since.insert(tx.hash, at);
+                    }
+                    None => {
+                        since.insert(tx.hash, now);
+                        update.entered.push((tx.clone(), queued_reason(pending, queued, tx.nonce)));
+                    }
+                }
+            }
+        }
+
+        // Whatever left the queued pool was promoted if it is pending now, otherwise dropped or mined
+        let pending: BTreeSet<H256> = content.pending.values().flat_map(|txs| txs.values()).map(|tx| tx.hash).collect();
+        for (hash, at) in self.since.drain() {
+            if pending.contains(&hash) {
+                update.promoted.insert(hash, now.duration_since(at));
+            }
+        }
+        self.since = since;
+
+        update
+    }
+}
+
+/// Find the nonce a queued transaction of a sender is waiting for
+fn queued_reason(
+    pending: Option<&BTreeMap<String, Transaction>>,
+    queued: &BTreeMap<String, Transaction>,
+    nonce: U256,
+) -> QueuedReason {
+    let highest_pending = pending.and_then(|txs| txs.values().map(|tx| tx.nonce).max());
+    let Some(highest_pending) = highest_pending else {
+        return QueuedReason::GapBelow(queued.values().map(|tx| tx.nonce).min().unwrap_or(nonce));
+    };
+
+    let known: BTreeSet<U256> = queued.values().map(|tx| tx.nonce).collect();
+    let mut next = highest_pending + 1;
+    while next < nonce {
+        if !known.contains(&next) {
+            return QueuedReason::MissingNonce(next);
+        }
+        next += U256::one();
+    }
+    QueuedReason::NotExecutable
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tx(hash: u8, from: Address, nonce: u64) -> Transaction {
+        Transaction { hash: H256::repeat_byte(hash), from, nonce: nonce.into(), ..Default::default() }
+    }
+
+    fn by_nonce(txs: &[&Transaction]) -> BTreeMap<String, Transaction> {
+        txs.iter().map(|tx| (tx.nonce.to_string(), (*tx).clone())).collect()
+    }
+
+    #[test]
+    fn explains_why_transactions_are_queued() {
+        let (a, b, c) = (Address::repeat_byte(0xa), Address::repeat_byte(0xb), Address::repeat_byte(0xc));
+        let content = TxpoolContent {
+            pending: BTreeMap::from([(a, by_nonce(&[&tx(1, a, 0)])), (c, by_nonce(&[&tx(5, c, 0)]))]),
+            queued: BTreeMap::from([
+                // Nonce 1 of `a` is missing
+                (a, by_nonce(&[&tx(2, a, 2)])),
+                // Nothing pending from `b`
+                (b, by_nonce(&[&tx(3, b, 4), &tx(4, b, 5)])),
+                // Next in line for `c`, stuck for another reason
+                (c, by_nonce(&[&tx(6, c, 1)])),
+            ]),
+        };
+
+        let update = QueuedPool::default().update(&content);
+        let reasons: HashMap<H256, QueuedReason> = update.entered.into_iter().map(|(tx, reason)| (tx.hash, reason)).collect();
+        assert_eq!(reasons[&H256::repeat_byte(2)], QueuedReason::MissingNonce(U256::one()));
+        assert_eq!(reasons[&H256::repeat_byte(3)], QueuedReason::GapBelow(U256::from(4)));
+        assert_eq!(reasons[&H256::repeat_byte(4)], QueuedReason::GapBelow(U256::from(4)));
+        assert_eq!(reasons[&H256::repeat_byte(6)], QueuedReason::NotExecutable);
+    }
+
+    #[test]
+    fn detects_promotions_between_snapshots() {
+        let sender = Address::repeat_byte(0xa);
+        let (first, gapped, dropped) = (tx(1, sender, 0), tx(2, sender, 1), tx(3, sender, 5));
+        let mut pool = QueuedPool::default();
+        let queued = TxpoolContent {
+            pending: BTreeMap::new(),
+            queued: BTreeMap::from([(sender, by_nonce(&[&gapped, &dropped]))]),
+        };
+        assert_eq!(pool.update(&queued).entered.len(), 2);
+        // Still queued, not reported again
+        assert!(pool.update(&queued).entered.is_empty());
+
+        let promoted = TxpoolContent {
+            pending: BTreeMap::from([(sender, by_nonce(&[&first, &gapped]))]),
+            queued: BTreeMap::new(),
+        };
+        let update = pool.update(&promoted);
+        assert!(update.entered.is_empty());
+        assert_eq!(update.promoted.keys().collect::<Vec<_>>(), vec![&gapped.hash]);
+    }
+}
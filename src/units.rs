@@ -0,0 +1,121 @@
+use ethers::prelude::*;
+
+use crate::utils::format_number;
+
+/// Denomination of an amount of ether
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Unit {
+    Wei,
+    Gwei,
+    Ether,
+}
+
+impl Unit {
+    /// Decimals between wei and this unit
+    pub fn decimals(self) -> u32 {
+        match self {
+            Unit::Wei => 0,
+            Unit::Gwei => 9,
+            Unit::Ether => 18,
+        }
+    }
+
+    pub fn symbol(self) -> &'static str {
+        match self {
+            Unit::Wei => "wei",
+            Unit::Gwei => "Gwei",
+            Unit::Ether => "ETH",
+        }
+    }
+}
+
+/// Format an integer amount of base units as a decimal number with `decimals` decimals
+///
+/// The conversion is exact, trailing zeros of the fraction are dropped and the integer part gets
+/// thousands separators. `max_fraction_digits` rounds the fraction half up to at most that many digits.
+pub fn format_units(amount: U256, decimals: u32, max_fraction_digits: Option<u32>) -> String {
+    let Some(scale) = U256::from(10).checked_pow(U256::from(decimals)) else {
+        // No U256 reaches a single unit with this many decimals
+        return format!("{}e-{}", amount, decimals);
+    };
+    let (mut integer, mut fraction) = amount.div_mod(scale);
+
+    let mut digits = decimals;
+    if let Some(max_digits) = max_fraction_digits.filter(|max_digits| *max_digits < decimals) {
+        let cut = U256::exp10((decimals - max_digits) as usize);
+        let (kept, rest) = fraction.div_mod(cut);
+        fraction = kept;
+        if rest >= cut - rest {
+            fraction += U256::one();
+            if fraction == U256::exp10(max_digits as usize) {
+                fraction = U256::zero();
+                integer += U256::one();
+            }
+        }
+        digits = max_digits;
+    }
+
+    let mut output = format_number(integer);
+    if !fraction.is_zero() {
+        let fraction = format!("{:0>width$}", fraction, width = digits as usize);
+        output.push('.');
+        output.push_str(fraction.trim_end_matches('0'));
+    }
+    output
+}
+
+/// Format an amount in wei in the given unit with its symbol, e.g. `1,234.5 Gwei`
+pub fn format_amount(wei: U256, unit: Unit, max_fraction_digits: Option<u32>) -> String {
+    format!("{} {}", format_units(wei, unit.decimals(), max_fraction_digits), unit.symbol())
+}
+
+/// Format a raw ERC-20 amount using the token's `decimals()` and symbol, e.g. `1,500.25 USDC`
+pub fn format_token_amount(amount: U256, decimals: u8, symbol: &str, max_fraction_digits: Option<u32>) -> String {
+    format!("{} {}", format_units(amount, decimals.into(), max_fraction_digits), symbol)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn formats_single_wei_exactly() {
+        assert_eq!(format_units(U256::one(), 18, None), "0.000000000000000001");
+        assert_eq!(format_amount(U256::one(), Unit::Wei, None), "1 wei");
+    }
+
+    #[test]
+    fn keeps_every_digit_below_one_ether() {
+        let almost_one = U256::exp10(18) - U256::one();
+        assert_eq!(format_units(almost_one, 18, None), "0.999999999999999999");
+    }
+
+    #[test]
+    fn formats_the_largest_amount() {
+        assert_eq!(
+            format_units(U256::MAX, 18, None),
+            "115,792,089,237,316,195,423,570,985,008,687,907,853,269,984,665,640,564,039,457.584007913129639935"
+        );
+        assert_eq!(format_units(U256::MAX, 0, None), format_number(U256::MAX));
+    }
+
+    #[test]
+    fn rounds_half_up_into_the_integer_part() {
+        let almost_one = U256::exp10(18) - U256::one();
+        assert_eq!(format_units(almost_one, 18, Some(2)), "1");
+        // 999.9995 ETH
+        assert_eq!(format_units(U256::from(9_999_995u64) * U256::exp10(14), 18, Some(3)), "1,000");
+        // 1.2345 and 1.2344999 Gwei
+        assert_eq!(format_amount(U256::from(1_234_500_000u64), Unit::Gwei, Some(3)), "1.235 Gwei");
+        assert_eq!(format_amount(U256::from(1_234_499_900u64), Unit::Gwei, Some(3)), "1.234 Gwei");
+        assert_eq!(format_units(U256::from(5), 1, Some(0)), "1");
+        assert_eq!(format_units(U256::MAX, 18, Some(0)), "115,792,089,237,316,195,423,570,985,008,687,907,853,269,984,665,640,564,039,458");
+    }
+
+    #[test]
+    fn formats_token_amounts_with_their_decimals() {
+        assert_eq!(format_token_amount(U256::from(1_500_250_000u64), 6, "USDC", None), "1,500.25 USDC");
+        assert_eq!(format_token_amount(U256::from(42), 0, "NFT", None), "42 NFT");
+        assert_eq!(format_token_amount(U256::one(), 255, "ODD", None), "1e-255 ODD");
+    }
+}
@@ -1,5 +1,6 @@
 use env_logger::{Builder, Env};
 use log::LevelFilter;
+use std::path::Path;
 use std::time::Duration;
 
 /// Setup logger with the specified log level
@@ -18,6 +19,29 @@ pub fn setup_logger(log_level: &str) {
         .init();
 }
 
+/// Read endpoints from a file, one per line, ignoring blank lines and `#` comments
+pub fn read_endpoints_file(path: &Path) -> std::io::Result<Vec<String>> {
+    let content = std::fs::read_to_string(path)?;
+    Ok(content
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(String::from)
+        .collect())
+}
+
+/// Parse a `METHOD=WEIGHT` pair used to price RPC methods in the rate limiter
+pub fn parse_method_weight(s: &str) -> Result<(String, u32), String> {
+    let (method, weight) = s
+        .split_once('=')
+        .ok_or_else(|| format!("invalid method weight '{}', expected METHOD=WEIGHT", s))?;
+    let weight = weight
+        .trim()
+        .parse::<u32>()
+        .map_err(|e| format!("invalid weight for {}: {}", method, e))?;
+    Ok((method.trim().to_string(), weight))
+}
+
 /// Calculate the current query rate based on the number of transactions and elapsed time
 pub fn calculate_query_rate(tx_count: usize, elapsed: Duration) -> f64 {
     let elapsed_secs = elapsed.as_secs_f64();
@@ -29,15 +53,14 @@ pub fn calculate_query_rate(tx_count: usize, elapsed: Duration) -> f64 {
 }
 
 /// Format a large number with commas for better readability
-#[allow(dead_code)]
-pub fn format_number(num: u64) -> String {
+pub fn format_number(num: impl ToString) -> String {
     let num_str = num.to_string();
     let mut result = String::new();
     let len = num_str.len();
     
     for (i, c) in num_str.chars().enumerate() {
         result.push(c);
-        if (len - i - 1) % 3 == 0 && i < len - 1 {
+        if (len - i - 1).is_multiple_of(3) && i < len - 1 {
             result.push(',');
         }
     }